@@ -1,107 +1,164 @@
-/// Detect a palindrome that is offset to the right, and return the
-/// number of elements to the left of the axis of symmetry.
+/// A kind of symmetry that a pattern can have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Symmetry {
+    /// Mirrored across a horizontal line, with this many rows above it
+    Horizontal(usize),
+    /// Mirrored across a vertical line, with this many columns to the left of it
+    Vertical(usize),
+    /// Unchanged by a 180° rotation
+    Rotational,
+    /// Square pattern mirrored across the top-left to bottom-right diagonal
+    Diagonal,
+    /// Square pattern mirrored across the top-right to bottom-left diagonal
+    AntiDiagonal,
+}
+
+impl Symmetry {
+    /// Contribution of this symmetry to the puzzle answer. Only reflection lines
+    /// are scored.
+    fn score(&self) -> usize {
+        match self {
+            Symmetry::Horizontal(num) => num * 100,
+            Symmetry::Vertical(num) => *num,
+            Symmetry::Rotational | Symmetry::Diagonal | Symmetry::AntiDiagonal => 0,
+        }
+    }
+}
+
+/// Find every axis of even reflection in the sequence, returning the number of
+/// elements to the left of each axis.
 ///
-/// To detect a left offset, reverse the input.
-fn detect_offset_palindrome(items: &[usize]) -> Option<usize> {
-    // An offset palindrome will always reach the end of one side
-    // of the array, so start there until we find a match
+/// Elements are bit-packed rows or columns, and `smudges` is the exact number
+/// of differing bits needed across the whole reflection for it to count.
+fn reflection_lines(items: &[usize], smudges: u32) -> Vec<usize> {
+    (1..items.len())
+        .filter(|&axis| {
+            // Walk outwards from the axis until one side runs out
+            let differences: u32 = items[..axis]
+                .iter()
+                .rev()
+                .zip(&items[axis..])
+                .map(|(a, b)| (a ^ b).count_ones())
+                .sum();
+
+            differences == smudges
+        })
+        .collect()
+}
+
+/// Hash a row / column into an integer by treating it as a binary sequence. This
+/// works as long as the inputs are less than 64 wide/tall - any other hashing
+/// function would be fine too.
+fn pack(bits: impl Iterator<Item = bool>) -> usize {
+    bits.fold(0, |accum, b| (accum << 1) + usize::from(b))
+}
+
+struct Pattern {
+    cells: Vec<Vec<bool>>,
+    /// Each row, treated as a binary sequence
+    rows: Vec<usize>,
+    /// Each column, treated as a binary sequence
+    cols: Vec<usize>,
+}
+
+impl Pattern {
+    fn parse(block: &str) -> Self {
+        let cells: Vec<Vec<bool>> = block
+            .lines()
+            .map(|l| l.chars().map(|c| c == '#').collect())
+            .collect();
 
-    let mut i_start = 0;
-    let mut j = items.len() - 1;
+        let rows = cells.iter().map(|row| pack(row.iter().copied())).collect();
+        let cols = (0..cells[0].len())
+            .map(|j| pack(cells.iter().map(|row| row[j])))
+            .collect();
 
-    // Advance the left pointer until it matches the last element
-    while items[i_start] != items[j] && i_start < j {
-        i_start += 1;
+        Self { cells, rows, cols }
     }
 
-    if i_start == j {
-        // Went through the whole list and didn't find a match
-        return None;
+    fn height(&self) -> usize {
+        self.cells.len()
     }
 
-    // There is a case where the left side happened to match the end,
-    // but not because it's in the palindrome. For example:
-    //   3 2 3 2 1 1 2 3
-    // The left 3 2 aren't part of the palindrome, so we need to
-    // progressively ignore them.
+    fn width(&self) -> usize {
+        self.cells[0].len()
+    }
+
+    /// Number of cells that differ from their partner under a 180° rotation,
+    /// counting each pair once.
+    fn rotational_differences(&self) -> u32 {
+        let (h, w) = (self.height(), self.width());
 
-    while i_start < j {
-        let mut i = i_start;
+        let differing = (0..h)
+            .flat_map(|i| (0..w).map(move |j| (i, j)))
+            .filter(|&(i, j)| self.cells[i][j] != self.cells[h - 1 - i][w - 1 - j])
+            .count() as u32;
+
+        differing / 2
+    }
+
+    /// Number of cells that differ from their partner across the main diagonal,
+    /// counting each pair once. Only meaningful for square patterns.
+    fn diagonal_differences(&self) -> u32 {
+        let n = self.height();
+
+        (0..n)
+            .flat_map(|i| ((i + 1)..n).map(move |j| (i, j)))
+            .filter(|&(i, j)| self.cells[i][j] != self.cells[j][i])
+            .count() as u32
+    }
 
-        while items[i] == items[j] && i < j {
-            i += 1;
-            j -= 1;
+    /// Number of cells that differ from their partner across the anti-diagonal,
+    /// counting each pair once. Only meaningful for square patterns.
+    fn anti_diagonal_differences(&self) -> u32 {
+        let n = self.height();
+
+        (0..n)
+            .flat_map(|i| (0..(n - 1 - i)).map(move |j| (i, j)))
+            .filter(|&(i, j)| self.cells[i][j] != self.cells[n - 1 - j][n - 1 - i])
+            .count() as u32
+    }
+
+    /// Every symmetry of the pattern that holds after fixing exactly `smudges` cells.
+    fn symmetries(&self, smudges: u32) -> Vec<Symmetry> {
+        let mut symmetries: Vec<Symmetry> = reflection_lines(&self.rows, smudges)
+            .into_iter()
+            .map(Symmetry::Horizontal)
+            .chain(
+                reflection_lines(&self.cols, smudges)
+                    .into_iter()
+                    .map(Symmetry::Vertical),
+            )
+            .collect();
+
+        if self.rotational_differences() == smudges {
+            symmetries.push(Symmetry::Rotational);
         }
 
-        // This problem in particular is for even number palindromes - the
-        // axis of symmetry must be between two elements, not on one. For a
-        // normal palindrome, this should be i >= j.
-        if i > j {
-            return Some(i);
+        if self.height() == self.width() {
+            if self.diagonal_differences() == smudges {
+                symmetries.push(Symmetry::Diagonal);
+            }
+
+            if self.anti_diagonal_differences() == smudges {
+                symmetries.push(Symmetry::AntiDiagonal);
+            }
         }
 
-        // Start the search again, from one more element further from the left
-        j = items.len() - 1;
-        i_start += 1;
+        symmetries
     }
-
-    None
 }
 
-fn solution(input: &str) -> usize {
-    // We basically need an offset palindrome recognizer. To make it easier,
-    // we start by treating each row/column as a binary sequence and converting each
-    // to an integer to make later processing easier. This works as long as the
-    // inputs are less than 64 wide/tall - any other hashing function would be fine too.
+/// Full symmetry analysis of every pattern in the input
+fn analyze(input: &str) -> Vec<Vec<Symmetry>> {
     input
         .split("\n\n")
-        .map(|block| {
-            // Hash each row / column into an integer
-
-            let rows: Vec<usize> = block
-                .lines()
-                .map(|l| {
-                    l.chars()
-                        .fold(0, |accum, c| (accum << 1) + if c == '#' { 1 } else { 0 })
-                })
-                .collect();
-
-            let num_cols = block.lines().nth(1).unwrap().len();
-            let num_rows = rows.len();
-
-            // Iterating over the columns is slightly less elegant, but the same thing
-            let cols: Vec<usize> = (0..num_cols)
-                .map(|j| {
-                    (0..num_rows)
-                        // Need (num_cols + 1) here to account for the \n character ending each line
-                        .map(move |i| block.as_bytes()[i * (num_cols + 1) + j])
-                        .fold(0, |accum, c| (accum << 1) + if c == b'#' { 1 } else { 0 })
-                })
-                .collect();
-
-            (rows, cols)
-        })
-        .map(|(rows, cols)| {
-            // Detect palindromes. detect_offset_palindrome only detects right-offset palindromes,
-            // so the input is reversed to check for left offsets.
-
-            if let Some(num) = detect_offset_palindrome(&rows) {
-                num * 100
-            } else if let Some(num) =
-                detect_offset_palindrome(&rows.iter().rev().cloned().collect::<Vec<_>>())
-            {
-                (rows.len() - num) * 100
-            } else if let Some(num) = detect_offset_palindrome(&cols) {
-                num
-            } else if let Some(num) =
-                detect_offset_palindrome(&cols.iter().rev().cloned().collect::<Vec<_>>())
-            {
-                cols.len() - num
-            } else {
-                unreachable!("Failed to detect any palindrome");
-            }
-        })
-        .sum()
+        .map(|block| Pattern::parse(block).symmetries(0))
+        .collect()
+}
+
+fn solution(input: &str) -> usize {
+    analyze(input).iter().flatten().map(Symmetry::score).sum()
 }
 
 fn main() {
@@ -123,28 +180,70 @@ mod tests {
         assert_eq!(res, 405);
     }
 
+    #[test]
+    fn test_example_symmetries() {
+        let input = include_str!("../example.txt");
+        let res = analyze(input);
+
+        assert_eq!(
+            res,
+            vec![vec![Symmetry::Vertical(5)], vec![Symmetry::Horizontal(4)]]
+        );
+    }
+
     #[test]
     fn test_normal_palindrome() {
         let a = [3, 2, 2, 3];
-        let res = detect_offset_palindrome(&a);
+        let res = reflection_lines(&a, 0);
 
-        assert_eq!(res, Some(2));
+        assert_eq!(res, vec![2]);
     }
 
     #[test]
     fn test_offset_palindrome() {
         let a = [3, 2, 3, 2, 2, 3];
-        let res = detect_offset_palindrome(&a);
+        let res = reflection_lines(&a, 0);
 
-        assert_eq!(res, Some(4));
+        assert_eq!(res, vec![4]);
     }
 
     #[test]
     fn test_no_palindrome() {
         let a = [3, 2, 3, 2, 3];
-        let res = detect_offset_palindrome(&a);
+        let res = reflection_lines(&a, 0);
+
+        assert_eq!(res, vec![]);
+    }
+
+    #[test]
+    fn test_multiple_reflection_lines() {
+        let a = [1, 1, 1, 1];
+        let res = reflection_lines(&a, 0);
+
+        assert_eq!(res, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_rotational_and_diagonal() {
+        let pattern = Pattern::parse("#..\n.#.\n..#");
+        let res = pattern.symmetries(0);
+
+        assert_eq!(
+            res,
+            vec![
+                Symmetry::Rotational,
+                Symmetry::Diagonal,
+                Symmetry::AntiDiagonal
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_square_skips_diagonals() {
+        let pattern = Pattern::parse("#..\n..#");
+        let res = pattern.symmetries(0);
 
-        assert_eq!(res, None);
+        assert_eq!(res, vec![Symmetry::Rotational]);
     }
 
     #[test]
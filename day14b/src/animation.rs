@@ -0,0 +1,243 @@
+//! Frame-by-frame recording of the platform as it's tilted, for playing back in
+//! the terminal or exporting as images. Every frame is a full copy of the board,
+//! so this is meant for boards around the size of the puzzle input.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::{find_cycle, rotate, Dir, Platform};
+
+struct Frame {
+    /// Number of complete program passes before this frame was captured
+    pass: usize,
+    /// The tilt that produced this frame, or None for the starting state
+    dir: Option<Dir>,
+    /// Set to the number of passes completed if this frame finishes a pass
+    /// (or is the starting state)
+    completes: Option<usize>,
+    board: String,
+}
+
+/// The first state that cycle detection found to repeat, as the passes after
+/// which it was first seen and seen again
+#[derive(Debug, PartialEq, Eq)]
+pub struct Repeat {
+    pub first: usize,
+    pub again: usize,
+}
+
+/// Captures the board after every tilt
+pub struct Recorder {
+    frames: Vec<Frame>,
+    passes: usize,
+    repeat: Option<Repeat>,
+}
+
+impl Recorder {
+    fn new(platform: &Platform) -> Self {
+        Self {
+            frames: vec![Frame {
+                pass: 0,
+                dir: None,
+                completes: Some(0),
+                board: platform.to_string(),
+            }],
+            passes: 0,
+            repeat: None,
+        }
+    }
+
+    fn rotate(&mut self, platform: &mut Platform, dir: Dir) {
+        rotate(platform, dir);
+
+        self.frames.push(Frame {
+            pass: self.passes,
+            dir: Some(dir),
+            completes: None,
+            board: platform.to_string(),
+        });
+    }
+
+    fn rotate_cycle(&mut self, platform: &mut Platform, program: &[Dir]) {
+        for &dir in program {
+            self.rotate(platform, dir);
+        }
+
+        self.passes += 1;
+        self.frames.last_mut().unwrap().completes = Some(self.passes);
+    }
+
+    /// Record `passes` passes of the program, or by default just enough to
+    /// reach the first repeated state
+    pub fn record(input: &str, program: &[Dir], passes: Option<usize>) -> Self {
+        let mut platform = Platform::parse(input);
+        let (cycle_start, cycle_length, _) = find_cycle(&platform, program);
+
+        let mut recorder = Self::new(&platform);
+        for _ in 0..passes.unwrap_or(cycle_start + cycle_length) {
+            recorder.rotate_cycle(&mut platform, program);
+        }
+
+        // Only mark the repeat if it made it into the recording
+        if recorder.passes >= cycle_start + cycle_length {
+            recorder.repeat = Some(Repeat {
+                first: cycle_start,
+                again: cycle_start + cycle_length,
+            });
+        }
+
+        recorder
+    }
+
+    pub fn repeat(&self) -> Option<&Repeat> {
+        self.repeat.as_ref()
+    }
+
+    /// Whether a frame holds the first repeated state, either time it was seen
+    fn is_repeat_frame(&self, index: usize) -> bool {
+        match (&self.repeat, self.frames[index].completes) {
+            (Some(repeat), Some(passes)) => passes == repeat.first || passes == repeat.again,
+            _ => false,
+        }
+    }
+
+    fn caption(&self, index: usize) -> String {
+        let frame = &self.frames[index];
+
+        let mut caption = match frame.dir {
+            None => "Start".to_string(),
+            Some(dir) => format!("Pass {}, tilt {:?}", frame.pass + 1, dir),
+        };
+
+        if self.is_repeat_frame(index) {
+            let repeat = self.repeat.as_ref().unwrap();
+            caption += &format!(
+                " [repeated state: after pass {} and pass {}]",
+                repeat.first, repeat.again
+            );
+        }
+
+        caption
+    }
+
+    /// Play the recording back in the terminal, pausing `delay` between frames
+    pub fn play(&self, delay: Duration) {
+        // Clear the screen
+        print!("\x1B[2J");
+
+        for index in 0..self.frames.len() {
+            // Move cursor to 0, 0
+            print!("\x1B[0;0H");
+            print!("{}", self.frames[index].board);
+            println!("{}\x1B[K", self.caption(index));
+            io::stdout().flush().unwrap();
+
+            // Linger on the repeated state so it's easy to spot
+            if self.is_repeat_frame(index) {
+                thread::sleep(delay * 10);
+            } else {
+                thread::sleep(delay);
+            }
+        }
+    }
+
+    /// Write every frame to `dir` as a numbered binary PPM image, with each
+    /// tile drawn as a `scale` pixel square. Frames holding the repeated state
+    /// get a red border.
+    pub fn write_ppm(&self, dir: &Path, scale: usize) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+
+        for index in 0..self.frames.len() {
+            let path = dir.join(format!("frame_{:05}.ppm", index));
+            fs::write(path, self.ppm(index, scale))?;
+        }
+
+        Ok(())
+    }
+
+    fn ppm(&self, index: usize, scale: usize) -> Vec<u8> {
+        let rows: Vec<&[u8]> = self.frames[index]
+            .board
+            .lines()
+            .map(|l| l.as_bytes())
+            .collect();
+
+        // One tile of padding all round, used for the border
+        let width = (rows[0].len() + 2) * scale;
+        let height = (rows.len() + 2) * scale;
+
+        let border: [u8; 3] = if self.is_repeat_frame(index) {
+            [220, 40, 40]
+        } else {
+            [255, 255, 255]
+        };
+
+        let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+
+        for y in 0..height {
+            for x in 0..width {
+                let (i, j) = (y / scale, x / scale);
+
+                let pixel = if i == 0 || j == 0 || i == rows.len() + 1 || j == rows[0].len() + 1 {
+                    border
+                } else {
+                    match rows[i - 1][j - 1] {
+                        b'#' => [60, 60, 70],
+                        b'O' => [230, 150, 30],
+                        _ => [235, 230, 215],
+                    }
+                };
+
+                image.extend_from_slice(&pixel);
+            }
+        }
+
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn test_record_until_repeat() {
+        let input = include_str!("../example.txt");
+        let program = parse_program("NWSE").unwrap();
+        let recorder = Recorder::record(input, &program, None);
+
+        let repeat = recorder.repeat().unwrap();
+        assert_eq!(recorder.frames.len(), 1 + repeat.again * program.len());
+
+        // The frames ending both passes hold the same board
+        let end_of = |pass: usize| &recorder.frames[pass * program.len()].board;
+        assert_eq!(end_of(repeat.first), end_of(repeat.again));
+        assert!(recorder.is_repeat_frame(repeat.first * program.len()));
+        assert!(recorder.is_repeat_frame(repeat.again * program.len()));
+        assert!(!recorder.is_repeat_frame(repeat.again * program.len() - 1));
+    }
+
+    #[test]
+    fn test_short_recording_has_no_repeat() {
+        let input = include_str!("../example.txt");
+        let program = parse_program("NWSE").unwrap();
+        let recorder = Recorder::record(input, &program, Some(1));
+
+        assert_eq!(recorder.frames.len(), 5);
+        assert_eq!(recorder.repeat(), None);
+    }
+
+    #[test]
+    fn test_ppm_size() {
+        let recorder = Recorder::new(&Platform::parse("O#\n..\n.."));
+        let image = recorder.ppm(0, 2);
+
+        let header = b"P6\n8 10\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 8 * 10 * 3);
+    }
+}
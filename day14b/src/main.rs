@@ -1,7 +1,12 @@
-use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
+mod animation;
 
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use animation::Recorder;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Dir {
     North,
     South,
@@ -9,121 +14,526 @@ enum Dir {
     West,
 }
 
-type Board = Vec<Vec<char>>;
-type I = Box<dyn Iterator<Item = usize>>;
+impl TryFrom<char> for Dir {
+    type Error = String;
 
-fn rotate(board: &mut Board, dir: Dir) {
-    let rows = board.len();
-    let cols = board[0].len();
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            'N' => Ok(Dir::North),
+            'S' => Ok(Dir::South),
+            'E' => Ok(Dir::East),
+            'W' => Ok(Dir::West),
+            _ => Err(format!(
+                "Unknown tilt direction {:?}, expected one of NSEW",
+                c
+            )),
+        }
+    }
+}
 
-    let row_iter: I = match dir {
-        Dir::South => Box::new((0..rows).rev()),
-        _ => Box::new(0..rows),
-    };
+/// Parse a tilt program such as `NWSE` into the directions to tilt in, in order
+fn parse_program(program: &str) -> Result<Vec<Dir>, String> {
+    program.chars().map(Dir::try_from).collect()
+}
+
+/// Total load on each edge of the platform
+#[derive(Debug, PartialEq, Eq)]
+struct Loads {
+    north: usize,
+    south: usize,
+    east: usize,
+    west: usize,
+}
+
+/// Fixed-length set of bits, packed into 64-bit words
+#[derive(Clone, PartialEq, Eq)]
+struct BitSet {
+    len: usize,
+    words: Vec<u64>,
+}
 
-    for i in row_iter {
-        let col_iter: I = match dir {
-            Dir::East => Box::new((0..cols).rev()),
-            _ => Box::new(0..cols),
+impl BitSet {
+    fn new(len: usize) -> Self {
+        Self {
+            len,
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn set(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    /// Iterate over the words overlapping the range [start, end), along with
+    /// a mask selecting the bits of each word that fall within the range
+    fn masks(start: usize, end: usize) -> impl Iterator<Item = (usize, u64)> {
+        let words = if start < end {
+            start / 64..end.div_ceil(64)
+        } else {
+            0..0
         };
 
-        for j in col_iter {
-            if board[i][j] == 'O' {
-                let swap_iter: I = match dir {
-                    Dir::North => Box::new((0..i).rev()),
-                    Dir::South => Box::new((i + 1)..rows),
-                    Dir::East => Box::new((j + 1)..cols),
-                    Dir::West => Box::new((0..j).rev()),
-                };
+        words.map(move |w| {
+            let lo = start.max(w * 64) - w * 64;
+            let hi = end.min((w + 1) * 64) - w * 64;
 
-                let source_offset: i32 = match dir {
-                    Dir::North | Dir::West => 1,
-                    Dir::South | Dir::East => -1,
-                };
+            (w, Self::mask(lo, hi - lo))
+        })
+    }
+
+    fn count_range(&self, start: usize, end: usize) -> usize {
+        Self::masks(start, end)
+            .map(|(w, mask)| (self.words[w] & mask).count_ones() as usize)
+            .sum()
+    }
+
+    fn clear_range(&mut self, start: usize, end: usize) {
+        for (w, mask) in Self::masks(start, end) {
+            self.words[w] &= !mask;
+        }
+    }
+
+    fn fill_range(&mut self, start: usize, end: usize) {
+        for (w, mask) in Self::masks(start, end) {
+            self.words[w] |= mask;
+        }
+    }
+
+    /// Move all the set bits in [start, end) to one end of the range
+    fn pack_range(&mut self, start: usize, end: usize, towards_start: bool) {
+        if start / 64 == (end - 1) / 64 {
+            // Fast path for the common case of a range within one word
+            let w = start / 64;
+            let mask = Self::mask(start - w * 64, end - start);
+            let count = (self.words[w] & mask).count_ones() as usize;
+
+            let filled = match (count, towards_start) {
+                (0, _) => 0,
+                (_, true) => Self::mask(start - w * 64, count),
+                (_, false) => Self::mask(end - count - w * 64, count),
+            };
+
+            self.words[w] = (self.words[w] & !mask) | filled;
+            return;
+        }
+
+        let count = self.count_range(start, end);
+        if count > 0 {
+            self.clear_range(start, end);
+
+            if towards_start {
+                self.fill_range(start, start + count);
+            } else {
+                self.fill_range(end - count, end);
+            }
+        }
+    }
+
+    /// `width` set bits starting from bit `lo` of a word
+    fn mask(lo: usize, width: usize) -> u64 {
+        (u64::MAX >> (64 - width)) << lo
+    }
+
+    /// Index of the first set bit at or after `start`
+    fn next_set(&self, start: usize) -> Option<usize> {
+        Self::masks(start, self.len)
+            .find(|(w, mask)| self.words[*w] & mask != 0)
+            .map(|(w, mask)| w * 64 + (self.words[w] & mask).trailing_zeros() as usize)
+    }
+
+    /// Indices of all set bits, in ascending order
+    fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, &word)| {
+            let mut word = word;
 
-                for k in swap_iter {
-                    let (target_row, target_col) = match dir {
-                        Dir::North | Dir::South => (k, j),
-                        Dir::East | Dir::West => (i, k),
-                    };
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(w * 64 + bit)
+            })
+        })
+    }
+}
+
+/// Which way the round rocks are currently packed
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// One bitset per column, indexed by row. Used for north/south tilts.
+    Cols,
+    /// One bitset per row, indexed by column. Used for east/west tilts.
+    Rows,
+}
+
+/// The open stretches between cube rocks along one line, as [start, end) ranges
+type Runs = Vec<(usize, usize)>;
+
+/// Split a line into the runs between its cube rocks
+fn find_runs(cubes: &BitSet) -> Runs {
+    let mut runs = Runs::new();
+    let mut start = 0;
+
+    while start < cubes.len {
+        let end = cubes.next_set(start).unwrap_or(cubes.len);
+        if end > start {
+            runs.push((start, end));
+        }
+
+        start = end + 1;
+    }
+
+    runs
+}
+
+#[derive(Clone)]
+struct Platform {
+    width: usize,
+    height: usize,
+    /// Cube rocks, one bitset per row
+    cubes: Vec<BitSet>,
+    // Cube rocks never move, so the runs between them are found once for
+    // each layout
+    col_runs: Vec<Runs>,
+    row_runs: Vec<Runs>,
+    round: Vec<BitSet>,
+    layout: Layout,
+}
+
+impl Platform {
+    fn parse(input: &str) -> Self {
+        let height = input.lines().count();
+        let width = input.lines().next().unwrap().len();
 
-                    if board[target_row][target_col] != '.' {
-                        break;
+        let mut cube_cols = vec![BitSet::new(height); width];
+        let mut cube_rows = vec![BitSet::new(width); height];
+        let mut round = vec![BitSet::new(height); width];
+
+        for (i, line) in input.lines().enumerate() {
+            for (j, c) in line.chars().enumerate() {
+                match c {
+                    '#' => {
+                        cube_cols[j].set(i);
+                        cube_rows[i].set(j);
                     }
+                    'O' => round[j].set(i),
+                    '.' => {}
+                    _ => panic!("Unknown tile {}", c),
+                }
+            }
+        }
 
-                    let (source_row, source_col) = match dir {
-                        Dir::North | Dir::South => ((k as i32 + source_offset) as usize, j),
-                        Dir::East | Dir::West => (i, (k as i32 + source_offset) as usize),
-                    };
+        Self {
+            width,
+            height,
+            col_runs: cube_cols.iter().map(find_runs).collect(),
+            row_runs: cube_rows.iter().map(find_runs).collect(),
+            cubes: cube_rows,
+            round,
+            layout: Layout::Cols,
+        }
+    }
+
+    /// Repack the round rocks into the given layout, by transposing the bit
+    /// matrix in 64x64 blocks
+    fn set_layout(&mut self, layout: Layout) {
+        if self.layout == layout {
+            return;
+        }
+
+        let (lines, len) = match layout {
+            Layout::Cols => (self.width, self.height),
+            Layout::Rows => (self.height, self.width),
+        };
+
+        let mut round = vec![BitSet::new(len); lines];
+        let mut block = [0u64; 64];
 
-                    board[source_row][source_col] = '.';
-                    board[target_row][target_col] = 'O';
+        for (bi, chunk) in self.round.chunks(64).enumerate() {
+            for bw in 0..chunk[0].words.len() {
+                block.fill(0);
+                for (k, line) in chunk.iter().enumerate() {
+                    block[k] = line.words[bw];
+                }
+
+                transpose_block(&mut block);
+
+                for (r, &word) in block.iter().enumerate().take(lines - bw * 64) {
+                    round[bw * 64 + r].words[bi] = word;
                 }
             }
         }
+
+        self.round = round;
+        self.layout = layout;
+    }
+
+    fn is_round(&self, row: usize, col: usize) -> bool {
+        match self.layout {
+            Layout::Cols => self.round[col].get(row),
+            Layout::Rows => self.round[row].get(col),
+        }
+    }
+
+    /// Positions of all the round rocks, as (row, col)
+    fn round_rocks(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.round.iter().enumerate().flat_map(move |(i, line)| {
+            line.ones().map(move |j| match self.layout {
+                Layout::Cols => (j, i),
+                Layout::Rows => (i, j),
+            })
+        })
+    }
+
+    /// The load on each edge, where each rock contributes its distance from the
+    /// opposite edge
+    fn loads(&self) -> Loads {
+        self.round_rocks().fold(
+            Loads {
+                north: 0,
+                south: 0,
+                east: 0,
+                west: 0,
+            },
+            |loads, (row, col)| Loads {
+                north: loads.north + self.height - row,
+                south: loads.south + row + 1,
+                east: loads.east + col + 1,
+                west: loads.west + self.width - col,
+            },
+        )
     }
 }
 
-fn rotate_cycle(board: &mut Board) {
-    rotate(board, Dir::North);
-    rotate(board, Dir::West);
-    rotate(board, Dir::South);
-    rotate(board, Dir::East);
+/// Platforms are only ever compared against later states of themselves, and the
+/// cube rocks never move, so only the round rocks need checking.
+impl PartialEq for Platform {
+    fn eq(&self, other: &Self) -> bool {
+        self.layout == other.layout && self.round == other.round
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in 0..self.height {
+            for j in 0..self.width {
+                let c = if self.cubes[i].get(j) {
+                    '#'
+                } else if self.is_round(i, j) {
+                    'O'
+                } else {
+                    '.'
+                };
+
+                write!(f, "{}", c)?;
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
 }
 
-fn hash_board(board: &Board) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    board.hash(&mut hasher);
-    hasher.finish()
+/// Transpose a 64x64 bit matrix in place, so that bit `j` of word `i` ends up
+/// as bit `i` of word `j`. Swaps progressively smaller off-diagonal sub-blocks.
+fn transpose_block(block: &mut [u64; 64]) {
+    let mut j = 32;
+    let mut mask: u64 = 0x0000_0000_FFFF_FFFF;
+
+    while j != 0 {
+        let mut k = 0;
+        while k < 64 {
+            let t = ((block[k] >> j) ^ block[k + j]) & mask;
+            block[k] ^= t << j;
+            block[k + j] ^= t;
+            k = (k + j + 1) & !j;
+        }
+
+        j >>= 1;
+        mask ^= mask << j;
+    }
 }
 
-const ITERATIONS: usize = 1_000_000_000;
+fn rotate(platform: &mut Platform, dir: Dir) {
+    match dir {
+        Dir::North | Dir::South => platform.set_layout(Layout::Cols),
+        Dir::East | Dir::West => platform.set_layout(Layout::Rows),
+    }
 
-fn solution(input: &str) -> usize {
-    let mut board: Board = input.lines().map(|l| l.chars().collect()).collect();
+    let all_runs = match platform.layout {
+        Layout::Cols => &platform.col_runs,
+        Layout::Rows => &platform.row_runs,
+    };
 
-    // Map of hash to the last iteration we saw it in, used to detect
-    // cycles.
-    let mut hash_states: HashMap<u64, usize> = HashMap::new();
+    let towards_start = matches!(dir, Dir::North | Dir::West);
 
-    let mut i = 0;
-    let mut cycle_length = usize::MAX;
+    // Each run between cube rocks is just a count of round rocks, which all
+    // slide up against one end
+    for (round, runs) in platform.round.iter_mut().zip(all_runs) {
+        for &(start, end) in runs {
+            round.pack_range(start, end, towards_start);
+        }
+    }
+}
 
-    while i < ITERATIONS {
-        rotate_cycle(&mut board);
+/// Run one pass of a tilt program
+fn rotate_cycle(platform: &mut Platform, program: &[Dir]) {
+    for &dir in program {
+        rotate(platform, dir);
+    }
 
-        let hash = hash_board(&board);
+    // Always finish in the same layout so that states compare equal
+    platform.set_layout(Layout::Cols);
+}
+
+/// Find the loop that repeated passes of a tilt program eventually fall into,
+/// using Brent's algorithm. Returns the number of passes before the loop starts,
+/// the length of the loop, and the platform state at the start of the loop.
+///
+/// States are compared exactly rather than by hash, so a detected repeat is
+/// always a real one. Only a couple of states are held at once, which keeps
+/// memory flat even on very large boards.
+fn find_cycle(start: &Platform, program: &[Dir]) -> (usize, usize, Platform) {
+    // Find the loop length by racing the hare ahead in doubling windows
+    let mut power = 1;
+    let mut cycle_length = 1;
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+    rotate_cycle(&mut hare, program);
 
-        if let Some(state) = hash_states.get(&hash) {
-            cycle_length = i - state;
-            break;
+    while tortoise != hare {
+        if power == cycle_length {
+            tortoise = hare.clone();
+            power *= 2;
+            cycle_length = 0;
         }
 
-        hash_states.insert(hash, i);
-        i += 1;
+        rotate_cycle(&mut hare, program);
+        cycle_length += 1;
     }
 
-    // Jump ahead past all the cycles, then perform the last few
-    // iterations
-    i += ((ITERATIONS - i) / cycle_length) * cycle_length + 1;
-    while i < ITERATIONS {
-        rotate_cycle(&mut board);
-        i += 1;
+    // With the hare exactly one loop ahead, they first meet where the loop starts
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+    for _ in 0..cycle_length {
+        rotate_cycle(&mut hare, program);
     }
 
-    board
-        .iter()
-        .rev()
-        .enumerate()
-        .map(|(i, row)| row.iter().filter(|c| **c == 'O').count() * (i + 1))
-        .sum()
+    let mut cycle_start = 0;
+    while tortoise != hare {
+        rotate_cycle(&mut tortoise, program);
+        rotate_cycle(&mut hare, program);
+        cycle_start += 1;
+    }
+
+    (cycle_start, cycle_length, tortoise)
+}
+
+/// The spin cycle from the puzzle
+const SPIN_CYCLE: &str = "NWSE";
+const ITERATIONS: usize = 1_000_000_000;
+
+fn solution(input: &str, program: &[Dir], iterations: usize) -> Loads {
+    let mut platform = Platform::parse(input);
+
+    let (cycle_start, cycle_length, cycle_state) = find_cycle(&platform, program);
+
+    // Jump ahead past all the cycles, then perform the last few iterations
+    let remaining = if iterations < cycle_start {
+        iterations
+    } else {
+        platform = cycle_state;
+        (iterations - cycle_start) % cycle_length
+    };
+
+    for _ in 0..remaining {
+        rotate_cycle(&mut platform, program);
+    }
+
+    platform.loads()
+}
+
+/// Ways to view the tilting, instead of just printing the result
+enum Visualise {
+    /// Play back in the terminal, with this long between frames
+    Animate(Duration),
+    /// Write each frame as a PPM image into this directory
+    Ppm(PathBuf),
 }
 
 fn main() {
+    // Usage: day14b [PROGRAM] [ITERATIONS] [--animate MS | --ppm DIR] [--passes N]
+    //
+    // PROGRAM and ITERATIONS default to the puzzle's spin cycle. --passes limits
+    // how many program passes are recorded, and otherwise recording stops at the
+    // first repeated state.
+    let mut positional = vec![];
+    let mut visualise = None;
+    let mut passes = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .unwrap_or_else(|| panic!("{} requires a value", name))
+        };
+
+        match arg.as_str() {
+            "--animate" => {
+                let ms = value("--animate")
+                    .parse()
+                    .expect("--animate takes milliseconds");
+                visualise = Some(Visualise::Animate(Duration::from_millis(ms)));
+            }
+            "--ppm" => visualise = Some(Visualise::Ppm(value("--ppm").into())),
+            "--passes" => {
+                passes = Some(value("--passes").parse().expect("--passes takes a number"))
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let program = positional.next().unwrap_or_else(|| SPIN_CYCLE.to_string());
+    let iterations = positional
+        .next()
+        .map(|n| {
+            n.parse()
+                .expect("ITERATIONS must be a non-negative integer")
+        })
+        .unwrap_or(ITERATIONS);
+
+    let program = parse_program(&program).unwrap_or_else(|err| panic!("{}", err));
+
     let input = include_str!("../input.txt");
-    let res = solution(input);
 
-    println!("Result: {}", res);
+    match visualise {
+        None => {
+            let res = solution(input, &program, iterations);
+
+            println!("Result: {}", res.north);
+            println!("{:?}", res);
+        }
+        Some(Visualise::Animate(delay)) => Recorder::record(input, &program, passes).play(delay),
+        Some(Visualise::Ppm(dir)) => {
+            let recorder = Recorder::record(input, &program, passes);
+            recorder.write_ppm(&dir, 4).expect("Failed to write frames");
+
+            if let Some(repeat) = recorder.repeat() {
+                println!(
+                    "First repeated state: after pass {} and pass {}",
+                    repeat.first, repeat.again
+                );
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -133,16 +543,127 @@ mod tests {
     #[test]
     fn test_example() {
         let input = include_str!("../example.txt");
-        let res = solution(input);
+        let program = parse_program(SPIN_CYCLE).unwrap();
+        let res = solution(input, &program, ITERATIONS);
+
+        assert_eq!(res.north, 64);
+    }
+
+    #[test]
+    fn test_single_tilt_program() {
+        // A single tilt north is part one of the puzzle
+        let input = include_str!("../example.txt");
+        let program = parse_program("N").unwrap();
+        let res = solution(input, &program, 1);
 
-        assert_eq!(res, 64);
+        assert_eq!(res.north, 136);
+    }
+
+    #[test]
+    fn test_repeated_tilt_is_idempotent() {
+        let input = include_str!("../example.txt");
+        let spin = solution(input, &parse_program("NWSE").unwrap(), 1000);
+        let doubled = solution(input, &parse_program("NNWWSSEE").unwrap(), 1000);
+
+        assert_eq!(spin, doubled);
+    }
+
+    #[test]
+    fn test_edge_loads() {
+        let input = "O..\n.#.\n...";
+        let res = solution(input, &parse_program("SE").unwrap(), 5);
+
+        // The rock ends up in the bottom-right corner
+        assert_eq!(
+            res,
+            Loads {
+                north: 1,
+                south: 3,
+                east: 3,
+                west: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_program() {
+        assert!(parse_program("NWX").is_err());
+    }
+
+    #[test]
+    fn test_example_cycles() {
+        let input = include_str!("../example.txt");
+        let mut platform = Platform::parse(input);
+        let program = parse_program(SPIN_CYCLE).unwrap();
+
+        rotate_cycle(&mut platform, &program);
+        assert_eq!(
+            platform.to_string(),
+            ".....#....\n\
+             ....#...O#\n\
+             ...OO##...\n\
+             .OO#......\n\
+             .....OOO#.\n\
+             .O#...O#.#\n\
+             ....O#....\n\
+             ......OOOO\n\
+             #...O###..\n\
+             #..OO#....\n"
+        );
+
+        rotate_cycle(&mut platform, &program);
+        rotate_cycle(&mut platform, &program);
+        assert_eq!(
+            platform.to_string(),
+            ".....#....\n\
+             ....#...O#\n\
+             .....##...\n\
+             ..O#......\n\
+             .....OOO#.\n\
+             .O#...O#.#\n\
+             ....O#...O\n\
+             .......OOO\n\
+             #...O###.O\n\
+             #.OOO#...O\n"
+        );
+    }
+
+    #[test]
+    fn test_bitset_ranges_across_words() {
+        let mut bits = BitSet::new(200);
+
+        bits.fill_range(60, 130);
+        assert_eq!(bits.count_range(0, 200), 70);
+        assert_eq!(bits.next_set(0), Some(60));
+        assert_eq!(bits.next_set(130), None);
+
+        bits.clear_range(62, 128);
+        assert_eq!(bits.ones().collect::<Vec<_>>(), vec![60, 61, 128, 129]);
+    }
+
+    #[test]
+    fn test_transpose_block() {
+        let mut block = [0u64; 64];
+        for (i, word) in block.iter_mut().enumerate() {
+            *word = (i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        }
+
+        let mut transposed = block;
+        transpose_block(&mut transposed);
+
+        for (i, word) in block.iter().enumerate() {
+            for (j, transposed_word) in transposed.iter().enumerate() {
+                assert_eq!((word >> j) & 1, (transposed_word >> i) & 1);
+            }
+        }
     }
 
     #[test]
     fn test_input() {
         let input = include_str!("../input.txt");
-        let res = solution(input);
+        let program = parse_program(SPIN_CYCLE).unwrap();
+        let res = solution(input, &program, ITERATIONS);
 
-        assert_eq!(res, 88680);
+        assert_eq!(res.north, 88680);
     }
 }
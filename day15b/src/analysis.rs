@@ -0,0 +1,228 @@
+//! Tools for seeing how labels spread over the boxes, and for picking labels
+//! that land in a particular box.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::{hash, LensMap, NUM_BOXES};
+
+/// Multiplicative inverse of 17 mod 256, since 17 * 241 = 4097 = 16 * 256 + 1.
+/// This undoes the multiply step of HASH.
+const INVERSE_17: u8 = 241;
+
+/// How a set of distinct labels is spread over the boxes
+pub struct Distribution {
+    buckets: Vec<Vec<String>>,
+}
+
+impl Distribution {
+    /// Bucket the labels from a list separated by commas and/or newlines.
+    /// Duplicates are only counted once.
+    pub fn from_list(list: &str) -> Self {
+        let labels: BTreeSet<&str> = list
+            .split([',', '\n'])
+            .map(str::trim)
+            .filter(|label| !label.is_empty())
+            .collect();
+
+        let mut buckets = vec![Vec::new(); NUM_BOXES];
+        for label in labels {
+            buckets[hash(label)].push(label.to_string());
+        }
+
+        Self { buckets }
+    }
+
+    /// Bucket the labels of the lenses in a map, which are already distinct
+    /// and in their boxes
+    pub fn from_lenses(lens_map: &LensMap) -> Self {
+        let mut buckets = vec![Vec::new(); NUM_BOXES];
+        for (i, lens) in lens_map.iter() {
+            buckets[i].push(lens.label.clone());
+        }
+
+        Self { buckets }
+    }
+
+    fn num_labels(&self) -> usize {
+        self.buckets.iter().map(Vec::len).sum()
+    }
+
+    fn occupied(&self) -> usize {
+        self.buckets.iter().filter(|b| !b.is_empty()).count()
+    }
+
+    fn max_load(&self) -> usize {
+        self.buckets.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    fn mean_load(&self) -> f64 {
+        self.num_labels() as f64 / NUM_BOXES as f64
+    }
+
+    /// Pearson's chi-squared statistic against a uniform spread. For a good hash
+    /// this should be close to the 255 degrees of freedom.
+    fn chi_squared(&self) -> f64 {
+        let expected = self.mean_load();
+        if expected == 0.0 {
+            return 0.0;
+        }
+
+        self.buckets
+            .iter()
+            .map(|b| (b.len() as f64 - expected).powi(2) / expected)
+            .sum()
+    }
+
+    /// Boxes holding more than one label, with the labels in each
+    fn collisions(&self) -> impl Iterator<Item = (usize, &[String])> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| (i, b.as_slice()))
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Labels: {}", self.num_labels())?;
+        writeln!(
+            f,
+            "Occupied boxes: {} / {} ({} empty)",
+            self.occupied(),
+            NUM_BOXES,
+            NUM_BOXES - self.occupied()
+        )?;
+        writeln!(
+            f,
+            "Load: mean {:.2}, max {}",
+            self.mean_load(),
+            self.max_load()
+        )?;
+        writeln!(f, "Chi-squared (255 dof): {:.1}", self.chi_squared())?;
+
+        let colliding_labels: usize = self.collisions().map(|(_, labels)| labels.len()).sum();
+        writeln!(
+            f,
+            "Collisions: {} boxes, {} labels",
+            self.collisions().count(),
+            colliding_labels
+        )?;
+
+        for (i, labels) in self.collisions() {
+            writeln!(f, "  Box {}: {}", i, labels.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Generate up to `count` lowercase labels of length `len` that hash to `target`.
+///
+/// Every prefix of `len - 1` letters is tried in order. Since HASH's multiply
+/// by 17 can be undone, the one byte that takes the prefix's hash to `target` is
+/// found directly, and kept if it's a letter.
+pub fn labels_for_box(len: usize, target: u8, count: usize) -> Vec<String> {
+    if len == 0 {
+        return if target == 0 && count > 0 {
+            vec![String::new()]
+        } else {
+            vec![]
+        };
+    }
+
+    let mut labels = vec![];
+    let mut prefix = vec![b'a'; len - 1];
+
+    while labels.len() < count {
+        let state = hash(std::str::from_utf8(&prefix).unwrap()) as u8;
+        let last = target.wrapping_mul(INVERSE_17).wrapping_sub(state);
+
+        if last.is_ascii_lowercase() {
+            let mut label = prefix.clone();
+            label.push(last);
+            labels.push(String::from_utf8(label).unwrap());
+        }
+
+        // Advance the prefix like an odometer, stopping once it wraps around
+        let Some(pos) = prefix.iter().rposition(|&c| c != b'z') else {
+            break;
+        };
+        prefix[pos] += 1;
+        prefix[pos + 1..].fill(b'a');
+    }
+
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inverse() {
+        assert_eq!(17u8.wrapping_mul(INVERSE_17), 1);
+    }
+
+    #[test]
+    fn test_labels_for_box() {
+        for target in [0, 3, 128, 255] {
+            let labels = labels_for_box(3, target, 20);
+
+            assert_eq!(labels.len(), 20);
+            for label in labels {
+                assert_eq!(label.len(), 3);
+                assert_eq!(hash(&label), target as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn test_labels_for_box_exhausts_short_labels() {
+        // Only one single letter hashes to each box, if any do
+        let labels = labels_for_box(1, hash("q") as u8, 10);
+        assert_eq!(labels, vec!["q"]);
+
+        assert_eq!(labels_for_box(0, 0, 10), vec![""]);
+        assert!(labels_for_box(0, 1, 10).is_empty());
+    }
+
+    #[test]
+    fn test_distribution_from_lenses() {
+        let lens_map = crate::initialize(include_str!("../example.txt"), None).unwrap();
+        let distribution = Distribution::from_lenses(&lens_map);
+
+        // Labels stay in the order their lenses are in the box
+        assert_eq!(distribution.num_labels(), 5);
+        assert_eq!(
+            distribution.collisions().collect::<Vec<_>>(),
+            vec![
+                (0, ["rn".to_string(), "cm".to_string()].as_slice()),
+                (
+                    3,
+                    ["ot".to_string(), "ab".to_string(), "pc".to_string()].as_slice()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_distribution() {
+        let distribution = Distribution::from_list("rn,cm\nqp,rn,pc,ot,ab,");
+
+        assert_eq!(distribution.num_labels(), 6);
+        assert_eq!(distribution.occupied(), 3);
+        assert_eq!(distribution.max_load(), 3);
+        assert_eq!(
+            distribution.collisions().collect::<Vec<_>>(),
+            vec![
+                (0, ["cm".to_string(), "rn".to_string()].as_slice()),
+                (
+                    3,
+                    ["ab".to_string(), "ot".to_string(), "pc".to_string()].as_slice()
+                ),
+            ]
+        );
+    }
+}
@@ -1,84 +1,568 @@
+mod analysis;
+
+use std::fmt;
+use std::hash::Hasher;
+use std::io::{self, Write};
+
+/// The Holiday ASCII String Helper algorithm from the puzzle.
+///
+/// Note that hashing a `&str` through `Hash` also writes a terminator byte, so
+/// labels should be fed in with `write` directly, as `hash` does.
+#[derive(Default)]
+struct HolidayHasher {
+    state: u8,
+}
+
+impl Hasher for HolidayHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state = self.state.wrapping_add(byte).wrapping_mul(17);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.state as u64
+    }
+}
+
+fn hash(text: &str) -> usize {
+    let mut hasher = HolidayHasher::default();
+    hasher.write(text.as_bytes());
+    hasher.finish() as usize
+}
+
+const NUM_BOXES: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Lens {
     label: String,
     focal_length: usize,
 }
 
-fn hash(text: &str) -> usize {
-    text.as_bytes()
-        .iter()
-        .fold(0, |hash: usize, char| ((hash + *char as usize) * 17) % 256)
-}
-
-fn solution(input: &str) -> usize {
-    let mut boxes: Vec<Vec<Lens>> = (0..256).map(|_| Vec::new()).collect();
-
-    input.split(',').for_each(|text| {
-        let label: String = text.chars().take_while(|c| c.is_alphabetic()).collect();
-        let box_idx = hash(&label);
-
-        if text.ends_with('-') {
-            boxes[box_idx].retain(|lens| lens.label != label);
-        } else {
-            let focal_length = text
-                .chars()
-                .filter(|c| c.is_numeric())
-                .collect::<String>()
-                .parse()
-                .unwrap();
-
-            let mut found = false;
-            for lens in boxes[box_idx].iter_mut() {
-                if lens.label == label {
-                    lens.focal_length = focal_length;
-                    found = true;
-                    break;
-                }
-            }
+/// The HASHMAP: lenses are kept in the box given by the hash of their label,
+/// in the order they were first inserted
+struct LensMap {
+    boxes: Vec<Vec<Lens>>,
+}
 
-            if !found {
-                boxes[box_idx].push(Lens {
-                    label,
+impl LensMap {
+    fn new() -> Self {
+        Self {
+            boxes: (0..NUM_BOXES).map(|_| Vec::new()).collect(),
+        }
+    }
+
+    /// Put a lens in its box, replacing any lens with the same label in place.
+    /// Returns the focal length of the replaced lens.
+    fn insert(&mut self, label: &str, focal_length: usize) -> Option<usize> {
+        let lenses = &mut self.boxes[hash(label)];
+
+        match lenses.iter_mut().find(|lens| lens.label == label) {
+            Some(lens) => Some(std::mem::replace(&mut lens.focal_length, focal_length)),
+            None => {
+                lenses.push(Lens {
+                    label: label.to_string(),
                     focal_length,
                 });
+                None
             }
         }
-    });
+    }
 
-    boxes
-        .iter()
-        .enumerate()
-        .flat_map(|(i, lenses)| {
-            lenses
-                .iter()
-                .enumerate()
-                .map(move |(j, lens)| (i + 1) * (j + 1) * lens.focal_length)
+    /// Take the lens with the given label out of its box, shifting the ones
+    /// behind it forward. Returns its focal length.
+    fn remove(&mut self, label: &str) -> Option<usize> {
+        let lenses = &mut self.boxes[hash(label)];
+        let idx = lenses.iter().position(|lens| lens.label == label)?;
+
+        Some(lenses.remove(idx).focal_length)
+    }
+
+    /// The focal length of the lens with the given label, if it's in its box
+    fn get(&self, label: &str) -> Option<usize> {
+        self.boxes[hash(label)]
+            .iter()
+            .find(|lens| lens.label == label)
+            .map(|lens| lens.focal_length)
+    }
+
+    /// The lenses in one box, front to back
+    fn lenses_in(&self, box_idx: usize) -> &[Lens] {
+        &self.boxes[box_idx]
+    }
+
+    /// All lenses along with their box number, from the first box to the last
+    /// and front to back within each box
+    fn iter(&self) -> impl Iterator<Item = (usize, &Lens)> {
+        self.boxes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, lenses)| lenses.iter().map(move |lens| (i, lens)))
+    }
+
+    fn focusing_power(&self) -> usize {
+        self.boxes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, lenses)| {
+                lenses
+                    .iter()
+                    .enumerate()
+                    .map(move |(j, lens)| (i + 1) * (j + 1) * lens.focal_length)
+            })
+            .sum()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Step<'a> {
+    /// `label-`
+    Remove { label: &'a str },
+    /// `label=focal_length`
+    Insert { label: &'a str, focal_length: usize },
+}
+
+impl Step<'_> {
+    fn label(&self) -> &str {
+        match self {
+            Step::Remove { label } | Step::Insert { label, .. } => label,
+        }
+    }
+}
+
+impl fmt::Display for Step<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Remove { label } => write!(f, "{}-", label),
+            Step::Insert {
+                label,
+                focal_length,
+            } => write!(f, "{}={}", label, focal_length),
+        }
+    }
+}
+
+/// Problems with the initialization sequence. Offsets are in bytes from the
+/// start of the input.
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError {
+    /// A step with nothing before its operation
+    EmptyLabel { offset: usize },
+    /// A character that can't appear at this point in a step
+    Unexpected { offset: usize, found: char },
+    /// A step that ends without a `-` or `=`
+    MissingOperation { offset: usize },
+    /// An `=` with no focal length after it
+    MissingFocalLength { offset: usize },
+    /// A focal length too large to represent
+    FocalLengthOverflow { offset: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::EmptyLabel { offset } => write!(f, "empty label at byte {}", offset),
+            ParseError::Unexpected { offset, found } => {
+                write!(f, "unexpected {:?} at byte {}", found, offset)
+            }
+            ParseError::MissingOperation { offset } => {
+                write!(f, "expected '-' or '=' at byte {}", offset)
+            }
+            ParseError::MissingFocalLength { offset } => {
+                write!(f, "expected a focal length at byte {}", offset)
+            }
+            ParseError::FocalLengthOverflow { offset } => {
+                write!(f, "focal length too large at byte {}", offset)
+            }
+        }
+    }
+}
+
+/// Parse a single step, which starts `offset` bytes into the input.
+///
+/// Labels are letters only, followed by either `-`, or `=` and a focal length.
+fn parse_step(text: &str, offset: usize) -> Result<Step<'_>, ParseError> {
+    let label_len = text
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(text.len());
+    let (label, rest) = text.split_at(label_len);
+    let rest_offset = offset + label_len;
+
+    let mut rest_chars = rest.chars();
+    let step = match rest_chars.next() {
+        None => Err(ParseError::MissingOperation {
+            offset: rest_offset,
+        }),
+        Some('-') => match rest_chars.next() {
+            // Nothing is allowed after a `-`
+            Some(found) => Err(ParseError::Unexpected {
+                offset: rest_offset + 1,
+                found,
+            }),
+            None => Ok(Step::Remove { label }),
+        },
+        Some('=') => {
+            parse_focal_length(&rest[1..], rest_offset + 1).map(|focal_length| Step::Insert {
+                label,
+                focal_length,
+            })
+        }
+        Some(found) => Err(ParseError::Unexpected {
+            offset: rest_offset,
+            found,
+        }),
+    }?;
+
+    if label.is_empty() {
+        return Err(ParseError::EmptyLabel { offset });
+    }
+
+    Ok(step)
+}
+
+fn parse_focal_length(digits: &str, offset: usize) -> Result<usize, ParseError> {
+    if digits.is_empty() {
+        return Err(ParseError::MissingFocalLength { offset });
+    }
+
+    if let Some((i, found)) = digits.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        return Err(ParseError::Unexpected {
+            offset: offset + i,
+            found,
+        });
+    }
+
+    digits
+        .parse()
+        .map_err(|_| ParseError::FocalLengthOverflow { offset })
+}
+
+/// Parse the comma-separated initialization sequence. A trailing newline is
+/// ignored.
+fn parse_steps(input: &str) -> Result<Vec<Step<'_>>, ParseError> {
+    let mut offset = 0;
+
+    input
+        .trim_end_matches(['\n', '\r'])
+        .split(',')
+        .map(|text| {
+            let step = parse_step(text, offset);
+            offset += text.len() + 1;
+            step
         })
-        .sum()
+        .collect()
+}
+
+/// Run the initialization sequence. When a trace is given, each step is written
+/// to it along with the new contents of the box it changed, if any.
+fn solution(input: &str, trace: Option<&mut dyn Write>) -> Result<usize, ParseError> {
+    initialize(input, trace).map(|lens_map| lens_map.focusing_power())
+}
+
+/// The lenses left in the boxes after running the initialization sequence
+fn initialize(input: &str, mut trace: Option<&mut dyn Write>) -> Result<LensMap, ParseError> {
+    let mut lens_map = LensMap::new();
+
+    for step in parse_steps(input)? {
+        let changed = match step {
+            Step::Remove { label } => lens_map.remove(label).is_some(),
+            Step::Insert {
+                label,
+                focal_length,
+            } => {
+                // Putting in a lens that's already there doesn't change the box
+                let changed = lens_map.get(label) != Some(focal_length);
+                lens_map.insert(label, focal_length);
+                changed
+            }
+        };
+
+        if let Some(trace) = trace.as_mut() {
+            write_trace(trace, &lens_map, &step, changed).expect("Failed to write trace");
+        }
+    }
+
+    Ok(lens_map)
+}
+
+fn write_trace(
+    trace: &mut dyn Write,
+    lens_map: &LensMap,
+    step: &Step,
+    changed: bool,
+) -> io::Result<()> {
+    writeln!(trace, "After \"{}\":", step)?;
+
+    if changed {
+        let box_idx = hash(step.label());
+
+        write!(trace, "Box {}:", box_idx)?;
+        for lens in lens_map.lenses_in(box_idx) {
+            write!(trace, " [{} {}]", lens.label, lens.focal_length)?;
+        }
+        writeln!(trace)?;
+    }
+
+    writeln!(trace)
 }
 
 fn main() {
-    let input = include_str!("../input.txt");
-    let res = solution(input);
+    // Usage:
+    //   day15b [--trace]             run the initialization sequence, with --trace
+    //                                printing the boxes changed by each step
+    //   day15b analyze [FILE]        report how the labels in FILE spread over the boxes,
+    //                                or else those left in them by the puzzle input
+    //   day15b generate LEN BOX [N]  print N labels of length LEN that hash to BOX
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    println!("Result: {}", res);
+    match args.first().map(String::as_str) {
+        Some("analyze") => {
+            let distribution = match args.get(1) {
+                Some(path) => {
+                    let list = std::fs::read_to_string(path).expect("Failed to read label list");
+                    analysis::Distribution::from_list(&list)
+                }
+                None => match initialize(include_str!("../input.txt"), None) {
+                    Ok(lens_map) => analysis::Distribution::from_lenses(&lens_map),
+                    Err(err) => {
+                        eprintln!("Invalid initialization sequence: {}", err);
+                        std::process::exit(1);
+                    }
+                },
+            };
+
+            print!("{}", distribution);
+        }
+        Some("generate") => {
+            let len = args
+                .get(1)
+                .and_then(|n| n.parse().ok())
+                .expect("generate requires a label LEN");
+            let target = args
+                .get(2)
+                .and_then(|n| n.parse().ok())
+                .expect("generate requires a BOX from 0 to 255");
+            let count = args
+                .get(3)
+                .map_or(10, |n| n.parse().expect("N must be a number"));
+
+            for label in analysis::labels_for_box(len, target, count) {
+                println!("{}", label);
+            }
+        }
+        _ => {
+            let trace = args.iter().any(|arg| arg == "--trace");
+
+            let input = include_str!("../input.txt");
+            let mut stdout = io::stdout();
+            let res = solution(input, trace.then_some(&mut stdout as &mut dyn Write));
+
+            match res {
+                Ok(res) => println!("Result: {}", res),
+                Err(err) => {
+                    eprintln!("Invalid initialization sequence: {}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_example() {
         let input = include_str!("../example.txt");
-        let res = solution(input);
+        let res = solution(input, None).unwrap();
 
         assert_eq!(res, 145);
     }
 
+    #[test]
+    fn test_parse_steps() {
+        let res = parse_steps("rn=1,cm-,qp=10\n").unwrap();
+
+        assert_eq!(
+            res,
+            vec![
+                Step::Insert {
+                    label: "rn",
+                    focal_length: 1
+                },
+                Step::Remove { label: "cm" },
+                Step::Insert {
+                    label: "qp",
+                    focal_length: 10
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_steps("rn=1,ab="),
+            Err(ParseError::MissingFocalLength { offset: 8 })
+        );
+        assert_eq!(
+            parse_steps("rn=1,a1b=3"),
+            Err(ParseError::Unexpected {
+                offset: 6,
+                found: '1'
+            })
+        );
+        assert_eq!(
+            parse_steps("rn=1,=3"),
+            Err(ParseError::EmptyLabel { offset: 5 })
+        );
+        assert_eq!(
+            parse_steps("rn=1x"),
+            Err(ParseError::Unexpected {
+                offset: 4,
+                found: 'x'
+            })
+        );
+        assert_eq!(
+            parse_steps("rn-1"),
+            Err(ParseError::Unexpected {
+                offset: 3,
+                found: '1'
+            })
+        );
+        assert_eq!(
+            parse_steps("rn,cm-"),
+            Err(ParseError::MissingOperation { offset: 2 })
+        );
+        assert_eq!(
+            parse_steps("rn=99999999999999999999999"),
+            Err(ParseError::FocalLengthOverflow { offset: 3 })
+        );
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut trace = Vec::new();
+        solution("rn=1,cm-,qp=3,rn=1,qp-", Some(&mut trace)).unwrap();
+
+        assert_eq!(
+            String::from_utf8(trace).unwrap(),
+            "After \"rn=1\":\nBox 0: [rn 1]\n\n\
+             After \"cm-\":\n\n\
+             After \"qp=3\":\nBox 1: [qp 3]\n\n\
+             After \"rn=1\":\n\n\
+             After \"qp-\":\nBox 1:\n\n"
+        );
+    }
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(hash("HASH"), 52);
+        assert_eq!(hash("rn"), 0);
+        assert_eq!(hash("qp"), 1);
+    }
+
+    #[test]
+    fn test_lens_map() {
+        let mut lens_map = LensMap::new();
+
+        assert_eq!(lens_map.insert("rn", 1), None);
+        assert_eq!(lens_map.insert("cm", 2), None);
+        assert_eq!(lens_map.insert("rn", 5), Some(1));
+        assert_eq!(lens_map.get("rn"), Some(5));
+        assert_eq!(lens_map.remove("qp"), None);
+        assert_eq!(lens_map.remove("rn"), Some(5));
+        assert_eq!(lens_map.get("rn"), None);
+
+        let lenses: Vec<_> = lens_map
+            .iter()
+            .map(|(i, lens)| (i, lens.label.as_str()))
+            .collect();
+        assert_eq!(lenses, vec![(0, "cm")]);
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Insert(String, usize),
+        Remove(String),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        // A small alphabet of short labels, so that operations keep hitting the
+        // same labels and boxes
+        prop_oneof![
+            ("[a-d]{1,3}", 1..10usize)
+                .prop_map(|(label, focal_length)| Op::Insert(label, focal_length)),
+            "[a-d]{1,3}".prop_map(Op::Remove),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_matches_ordered_reference(ops in prop::collection::vec(op(), 0..200)) {
+            let mut lens_map = LensMap::new();
+
+            // Reference map that keeps entries in insertion order, updating in place
+            let mut reference: Vec<(String, usize)> = Vec::new();
+
+            for op in ops {
+                match op {
+                    Op::Insert(label, focal_length) => {
+                        let expected = match reference.iter_mut().find(|(l, _)| *l == label) {
+                            Some(entry) => Some(std::mem::replace(&mut entry.1, focal_length)),
+                            None => {
+                                reference.push((label.clone(), focal_length));
+                                None
+                            }
+                        };
+
+                        prop_assert_eq!(lens_map.insert(&label, focal_length), expected);
+                    }
+                    Op::Remove(label) => {
+                        let expected = reference
+                            .iter()
+                            .position(|(l, _)| *l == label)
+                            .map(|idx| reference.remove(idx).1);
+
+                        prop_assert_eq!(lens_map.remove(&label), expected);
+                    }
+                }
+            }
+
+            for (label, focal_length) in &reference {
+                prop_assert_eq!(lens_map.get(label), Some(*focal_length));
+            }
+
+            // Iteration is the reference order, grouped stably by box
+            let mut expected: Vec<(usize, Lens)> = reference
+                .iter()
+                .map(|(label, focal_length)| {
+                    (hash(label), Lens { label: label.clone(), focal_length: *focal_length })
+                })
+                .collect();
+            expected.sort_by_key(|(i, _)| *i);
+
+            let actual: Vec<(usize, Lens)> = lens_map.iter().map(|(i, lens)| (i, lens.clone())).collect();
+            prop_assert_eq!(&actual, &expected);
+
+            let power: usize = expected
+                .iter()
+                .enumerate()
+                .map(|(n, (i, lens))| {
+                    let slot = expected[..n].iter().filter(|(j, _)| j == i).count();
+                    (i + 1) * (slot + 1) * lens.focal_length
+                })
+                .sum();
+            prop_assert_eq!(lens_map.focusing_power(), power);
+        }
+    }
+
     #[test]
     fn test_input() {
         let input = include_str!("../input.txt");
-        let res = solution(input);
+        let res = solution(input, None).unwrap();
 
         assert_eq!(res, 239484);
     }
@@ -0,0 +1,272 @@
+//! The full record of where a beam went, rather than just how many tiles it lit.
+
+use std::fmt::Write;
+
+use crate::{Board, Coord, Dir};
+
+const ALL_DIRS: [Dir; 4] = [Dir::North, Dir::East, Dir::South, Dir::West];
+
+fn dir_bit(dir: Dir) -> u8 {
+    1 << dir as u8
+}
+
+/// Bits of `Illumination::marks`, for things that happened on a tile
+const SPLIT: u8 = 1;
+const LOOPED: u8 = 2;
+const MERGED: u8 = 4;
+
+/// The beam entering the board, which every other beam is split off from
+pub const FIRST_BEAM: usize = 0;
+
+pub struct Illumination {
+    /// Directions the beam passed through each tile in, as a mask of `dir_bit`s
+    dirs: Vec<Vec<u8>>,
+    /// The beam that first passed through each tile in each direction
+    owners: Vec<Vec<[usize; 4]>>,
+    /// The beam each beam was split off from, by beam number. A beam stops
+    /// where it splits, so everywhere the beams it came from went is behind it.
+    parents: Vec<Option<usize>>,
+    /// What happened on each tile, as a mask of `SPLIT`, `LOOPED` and
+    /// `MERGED`, so looking a tile up doesn't mean searching the lists below
+    marks: Vec<Vec<u8>>,
+    /// Tiles where the beam split, in the order they were first hit
+    splits: Vec<Coord>,
+    /// Where beams stopped by coming back round to somewhere already on their
+    /// own path, which they'd otherwise go round forever
+    looped: Vec<(Coord, Dir)>,
+    /// Where beams stopped by joining the path of another beam, split off
+    /// from theirs somewhere before
+    merged: Vec<(Coord, Dir)>,
+}
+
+impl Illumination {
+    pub fn new(board: &Board) -> Self {
+        Self {
+            dirs: board.inner.iter().map(|row| vec![0; row.len()]).collect(),
+            owners: board
+                .inner
+                .iter()
+                .map(|row| vec![[0; 4]; row.len()])
+                .collect(),
+            parents: vec![None],
+            marks: board.inner.iter().map(|row| vec![0; row.len()]).collect(),
+            splits: vec![],
+            looped: vec![],
+            merged: vec![],
+        }
+    }
+
+    /// Start a new beam split off from `beam`, returning its number
+    pub fn split_beam(&mut self, beam: usize) -> usize {
+        self.parents.push(Some(beam));
+        self.parents.len() - 1
+    }
+
+    /// Whether `beam` is `ancestor`, or was split off from it somewhere back
+    fn descends_from(&self, mut beam: usize, ancestor: usize) -> bool {
+        loop {
+            if beam == ancestor {
+                return true;
+            }
+            match self.parents[beam] {
+                Some(parent) => beam = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Mark a tile as passed through by `beam` heading in `dir`. Returns false,
+    /// and records whether the beam looped or merged, if it already had been.
+    pub fn visit(&mut self, coord: Coord, dir: Dir, beam: usize) -> bool {
+        let (i, j) = (coord.0 as usize, coord.1 as usize);
+
+        if self.dirs[i][j] & dir_bit(dir) != 0 {
+            if self.descends_from(beam, self.owners[i][j][dir as usize]) {
+                self.marks[i][j] |= LOOPED;
+                self.looped.push((coord, dir));
+            } else {
+                self.marks[i][j] |= MERGED;
+                self.merged.push((coord, dir));
+            }
+            return false;
+        }
+
+        self.dirs[i][j] |= dir_bit(dir);
+        self.owners[i][j][dir as usize] = beam;
+        true
+    }
+
+    pub fn record_split(&mut self, coord: Coord) {
+        let marks = &mut self.marks[coord.0 as usize][coord.1 as usize];
+
+        if *marks & SPLIT == 0 {
+            *marks |= SPLIT;
+            self.splits.push(coord);
+        }
+    }
+
+    fn marked(&self, coord: Coord, mark: u8) -> bool {
+        self.marks[coord.0 as usize][coord.1 as usize] & mark != 0
+    }
+
+    pub fn energised(&self) -> usize {
+        self.dirs
+            .iter()
+            .flatten()
+            .filter(|&&mask| mask != 0)
+            .count()
+    }
+
+    /// Directions the beam passed through a tile in
+    pub fn dirs_at(&self, coord: Coord) -> Vec<Dir> {
+        let mask = self.dirs[coord.0 as usize][coord.1 as usize];
+
+        ALL_DIRS
+            .into_iter()
+            .filter(|&dir| mask & dir_bit(dir) != 0)
+            .collect()
+    }
+
+    pub fn splits(&self) -> &[Coord] {
+        &self.splits
+    }
+
+    pub fn looped(&self) -> &[(Coord, Dir)] {
+        &self.looped
+    }
+
+    pub fn merged(&self) -> &[(Coord, Dir)] {
+        &self.merged
+    }
+
+    /// Summary of the splitters hit, and the beams that looped or merged
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+
+        writeln!(report, "Energised tiles: {}", self.energised()).unwrap();
+
+        writeln!(report, "Splitters hit: {}", self.splits().len()).unwrap();
+        for Coord(i, j) in self.splits() {
+            writeln!(report, "  ({}, {})", i, j).unwrap();
+        }
+
+        for (label, stops) in [
+            ("Beams looping back onto their own path", self.looped()),
+            ("Beams merging into another's path", self.merged()),
+        ] {
+            writeln!(report, "{}: {}", label, stops.len()).unwrap();
+            for (Coord(i, j), dir) in stops {
+                writeln!(report, "  ({}, {}) heading {:?}", i, j, dir).unwrap();
+            }
+        }
+
+        report
+    }
+
+    /// Draw the board with each tile coloured by how many directions the beam
+    /// passed through it in. Empty tiles show the beam's direction, or how many
+    /// directions if more than one, as in the puzzle's examples. Splitters that
+    /// were hit are in bold, tiles where a beam looped are underlined, and
+    /// tiles where one merged into another are in italics.
+    pub fn heatmap(&self, board: &Board) -> String {
+        // 256-colour backgrounds for 1 to 4 directions
+        const COLOURS: [u8; 4] = [25, 35, 178, 160];
+
+        let mut heatmap = String::new();
+
+        for (i, row) in board.inner.iter().enumerate() {
+            for (j, &tile) in row.iter().enumerate() {
+                let coord = Coord(i as i32, j as i32);
+                let dirs = self.dirs_at(coord);
+
+                if !dirs.is_empty() {
+                    write!(heatmap, "\x1B[48;5;{}m", COLOURS[dirs.len() - 1]).unwrap();
+                }
+                if self.marked(coord, SPLIT) {
+                    heatmap += "\x1B[1m";
+                }
+                if self.marked(coord, LOOPED) {
+                    heatmap += "\x1B[4m";
+                }
+                if self.marked(coord, MERGED) {
+                    heatmap += "\x1B[3m";
+                }
+
+                heatmap.push(match (tile, dirs.as_slice()) {
+                    ('.', [Dir::North]) => '^',
+                    ('.', [Dir::East]) => '>',
+                    ('.', [Dir::South]) => 'v',
+                    ('.', [Dir::West]) => '<',
+                    ('.', [_, ..]) => char::from_digit(dirs.len() as u32, 10).unwrap(),
+                    _ => tile,
+                });
+                heatmap += "\x1B[0m";
+            }
+            heatmap.push('\n');
+        }
+
+        heatmap += "Directions: ";
+        for (n, colour) in COLOURS.iter().enumerate() {
+            write!(heatmap, "\x1B[48;5;{}m {} \x1B[0m", colour, n + 1).unwrap();
+        }
+        heatmap +=
+            "  \x1B[1msplitter hit\x1B[0m  \x1B[4mbeam looped\x1B[0m  \x1B[3mbeam merged\x1B[0m\n";
+
+        heatmap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::illuminate_board;
+
+    #[test]
+    fn test_example_illumination() {
+        let board = Board::from_input(include_str!("../example.txt"));
+        let res = illuminate_board(&board, Coord(0, 0), Dir::East, None);
+
+        assert_eq!(res.energised(), 46);
+
+        // The first splitter sends the beam south, and it's never travelled north.
+        // A beam later comes back along the top row and is split again.
+        assert_eq!(res.splits()[0], Coord(0, 1));
+        assert_eq!(res.dirs_at(Coord(0, 1)), vec![Dir::East, Dir::West]);
+        assert_eq!(res.dirs_at(Coord(1, 1)), vec![Dir::South]);
+        assert_eq!(res.dirs_at(Coord(9, 9)), vec![]);
+
+        // Beams only ever stop on a state that's already lit
+        for &(coord, dir) in res.looped().iter().chain(res.merged()) {
+            assert!(res.dirs_at(coord).contains(&dir));
+        }
+    }
+
+    #[test]
+    fn test_looped_and_merged() {
+        // The splitter in the middle sends one beam up and round to the
+        // splitter on the right. That beam's west half comes back up through
+        // the middle splitter onto its own path, while its east half and the
+        // beam sent down from the middle run into each other.
+        let board = Board::from_input("./.\\.\n.|...\n.\\.-.");
+        let res = illuminate_board(&board, Coord(1, 0), Dir::East, None);
+
+        assert_eq!(res.looped(), &[(Coord(0, 1), Dir::North)]);
+        assert_eq!(res.merged(), &[(Coord(2, 4), Dir::East)]);
+
+        let report = res.report();
+        assert!(report.contains("own path: 1\n  (0, 1) heading North\n"));
+        assert!(report.contains("another's path: 1\n  (2, 4) heading East\n"));
+    }
+
+    #[test]
+    fn test_heatmap_marks_tiles() {
+        let board = Board::from_input(".|.\n...");
+        let res = illuminate_board(&board, Coord(0, 0), Dir::East, None);
+        let heatmap = res.heatmap(&board);
+
+        // The splitter is lit once and in bold, the tile after it is dark
+        assert!(heatmap.starts_with("\x1B[48;5;25m>\x1B[0m\x1B[48;5;25m\x1B[1m|\x1B[0m.\x1B[0m"));
+        assert_eq!(res.splits(), &[Coord(0, 1)]);
+        assert!(res.looped().is_empty() && res.merged().is_empty());
+    }
+}
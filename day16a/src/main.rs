@@ -1,16 +1,33 @@
-use std::collections::HashSet;
+mod illumination;
+mod optics;
+mod trace;
+
 use std::ops::Add;
+use std::path::PathBuf;
+
+use illumination::{Illumination, FIRST_BEAM};
+use optics::Optics;
+use trace::Trace;
 
 // There are a lot of similarities between these types and what we did in day 10
 struct Board {
     inner: Vec<Vec<char>>,
+    optics: Optics,
 }
 
 impl Board {
+    /// Build a board using the mirrors and splitters from the puzzle
     fn from_input(input: &str) -> Self {
-        Self {
-            inner: input.lines().map(|l| l.chars().collect()).collect(),
-        }
+        Self::with_optics(input, Optics::standard()).unwrap()
+    }
+
+    /// Build a board whose tiles behave according to the given table. Every tile
+    /// on the board must be in the table.
+    fn with_optics(input: &str, optics: Optics) -> Result<Self, String> {
+        let inner: Vec<Vec<char>> = input.lines().map(|l| l.chars().collect()).collect();
+        optics.check(inner.iter().flatten().copied())?;
+
+        Ok(Self { inner, optics })
     }
 
     fn get(&self, c: &Coord) -> Option<char> {
@@ -23,30 +40,6 @@ impl Board {
             .and_then(|row| row.get(c.1 as usize))
             .cloned()
     }
-
-    #[cfg(feature = "interactive")]
-    fn print_at(&self, coord: &Coord, dir: &Dir) {
-        // Move cursor to 0, 0
-        print!("\x1B[0;0H");
-
-        let direction_symbol = match dir {
-            Dir::North => '^',
-            Dir::East => '>',
-            Dir::South => 'v',
-            Dir::West => '<',
-        };
-
-        for (i, row) in self.inner.iter().enumerate() {
-            for (j, cell) in row.iter().enumerate() {
-                if &Coord(i as i32, j as i32) == coord {
-                    print!("{}", direction_symbol)
-                } else {
-                    print!("{}", cell);
-                }
-            }
-            println!();
-        }
-    }
 }
 
 /// A (row, col) coordinate pair or vector. Using i32 so that we can subtract
@@ -84,88 +77,143 @@ impl Add<&Dir> for Coord {
     }
 }
 
-/// Wait for an enter press
-#[cfg(feature = "interactive")]
-fn wait() {
-    use std::io::stdin;
-    stdin().read_line(&mut String::new()).unwrap();
-}
-
-fn solution(input: &str) -> usize {
-    // Set of coordinates and directions of illuminated cells, used to detect
-    // loops.
-    let mut illuminated: HashSet<(Coord, Dir)> = HashSet::new();
+/// Send a beam through the board, returning everywhere it went. Every step the
+/// beam takes is recorded to `trace`, if given.
+fn illuminate_board(
+    board: &Board,
+    start: Coord,
+    start_dir: Dir,
+    mut trace: Option<&mut Trace>,
+) -> Illumination {
+    // Directions each cell has been illuminated in, also used to detect loops
+    let mut illumination = Illumination::new(board);
 
-    let board = Board::from_input(input);
+    // Forked beams we have to keep track of, from their starting coordinate and
+    // direction, along with which beam they are
+    let mut beams: Vec<(Coord, Dir, usize)> = vec![(start, start_dir, FIRST_BEAM)];
 
-    // Forked beams we have to keep track of, from their starting coordinate and direction
-    let mut beams: Vec<(Coord, Dir)> = vec![(Coord(0, 0), Dir::East)];
-
-    while let Some((start, dir_start)) = beams.pop() {
+    while let Some((start, dir_start, mut beam)) = beams.pop() {
         let mut coord = start;
         let mut dir = dir_start;
 
         while let Some(char) = board.get(&coord) {
-            let key = (coord, dir);
-            if illuminated.contains(&key) {
+            if !illumination.visit(coord, dir, beam) {
                 break;
-            } else {
-                illuminated.insert(key);
             }
 
-            #[cfg(feature = "interactive")]
-            {
-                board.print_at(&coord, &dir);
-                println!("Beams: {:?}", beams);
-                crate::wait();
+            if let Some(trace) = trace.as_mut() {
+                trace.record(coord, dir, beams.len());
             }
 
-            // dir is the direction we're going, not the direction we're coming from
-            dir = match (char, &dir) {
-                ('.', _) => dir,
-                ('/', Dir::North) => Dir::East,
-                ('/', Dir::East) => Dir::North,
-                ('/', Dir::South) => Dir::West,
-                ('/', Dir::West) => Dir::South,
-                ('\\', Dir::North) => Dir::West,
-                ('\\', Dir::East) => Dir::South,
-                ('\\', Dir::South) => Dir::East,
-                ('\\', Dir::West) => Dir::North,
-                ('-', Dir::East) | ('-', Dir::West) => dir,
-                ('-', Dir::North) | ('-', Dir::South) => {
-                    // Enqueue the west direction, proceed with east
-                    beams.push((&coord + &Dir::West, Dir::West));
-                    Dir::East
-                }
-                ('|', Dir::North) | ('|', Dir::South) => dir,
-                ('|', Dir::East) | ('|', Dir::West) => {
-                    // Enqueue the south direction, proceed with north
-                    beams.push((&coord + &Dir::South, Dir::South));
-                    Dir::North
-                }
-                (c, d) => panic!("Unrecognized combination ({}, {:?})", c, d),
+            // dir is the direction we're going, not the direction we're coming from.
+            // Continue with the first outgoing direction, and enqueue any others.
+            let Some((first, rest)) = board.optics.outgoing(char, dir).split_first() else {
+                // Absorbed
+                break;
             };
+            // Every way out of a splitter is a new beam, so that a beam's own
+            // path is just its and those of the beams it was split off from
+            if !rest.is_empty() {
+                illumination.record_split(coord);
+                for other in rest {
+                    beams.push((coord + other, *other, illumination.split_beam(beam)));
+                }
+                beam = illumination.split_beam(beam);
+            }
+            dir = *first;
 
             coord = coord + &dir;
         }
     }
 
-    illuminated
-        .iter()
-        .map(|(coord, _)| coord)
-        .collect::<HashSet<_>>()
-        .len()
+    illumination
+}
+
+fn solution(input: &str) -> usize {
+    let board = Board::from_input(input);
+    illuminate_board(&board, Coord(0, 0), Dir::East, None).energised()
 }
 
 fn main() {
-    // Clear the screen
-    #[cfg(feature = "interactive")]
-    print!("\x1B[2J");
+    // Usage: day16a [--optics FILE] [--play] [--fps N] [--step N] [--export FILE]
+    //              [--heatmap]
+    //
+    // --optics loads the tile behaviours from FILE, in the format of optics.txt.
+    // --play replays the beam's path in the terminal at --fps frames per second
+    // (default 30), starting from --step. Without --play, --step just shows that
+    // one step. --export writes every step of the path to FILE as CSV.
+    // --heatmap shows the directions each tile was lit in, along with the
+    // splitters hit and where beams looped back onto their own path or merged
+    // into another's.
+    let mut play = false;
+    let mut heatmap = false;
+    let mut fps = 30.0;
+    let mut step = None;
+    let mut export: Option<PathBuf> = None;
+    let mut optics: Option<PathBuf> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("{} requires a value", arg))
+        };
+
+        match arg.as_str() {
+            "--play" => play = true,
+            "--heatmap" => heatmap = true,
+            "--fps" => fps = value().parse().expect("--fps takes a number"),
+            "--step" => step = Some(value().parse().expect("--step takes a step number")),
+            "--export" => export = Some(value().into()),
+            "--optics" => optics = Some(value().into()),
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
 
     let input = include_str!("../input.txt");
-    let res = solution(input);
+    let recording = play || step.is_some() || export.is_some();
+
+    if !recording && !heatmap && optics.is_none() {
+        let res = solution(input);
+
+        println!("Result: {}", res);
+        return;
+    }
+
+    let optics = match optics {
+        Some(path) => {
+            let config = std::fs::read_to_string(path).expect("Failed to read optics table");
+            Optics::parse(&config).unwrap_or_else(|err| panic!("Invalid optics table: {}", err))
+        }
+        None => Optics::standard(),
+    };
+
+    let board = Board::with_optics(input, optics).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut trace = Trace::default();
+    let res = illuminate_board(
+        &board,
+        Coord(0, 0),
+        Dir::East,
+        recording.then_some(&mut trace),
+    );
+
+    if let Some(path) = export {
+        trace.export(&path).expect("Failed to export trace");
+        println!("Exported {} steps to {}", trace.len(), path.display());
+    }
+
+    if play {
+        trace.play(&board, fps, step.unwrap_or(0));
+    } else if let Some(step) = step {
+        print!("{}", trace.frame(&board, step));
+    }
 
-    println!("Result: {}", res);
+    if heatmap {
+        print!("{}\n{}", res.heatmap(&board), res.report());
+    }
+
+    println!("Result: {}", res.energised());
 }
 
 #[cfg(test)]
@@ -180,6 +228,38 @@ mod tests {
         assert_eq!(res, 46);
     }
 
+    #[test]
+    fn test_extended_optics() {
+        let optics = || Optics::parse(include_str!("../extended_optics.txt")).unwrap();
+
+        // Splits three ways at the +: north leaves the board, east carries on,
+        // and south is swallowed by the absorber
+        let board = Board::with_optics("..+..\n.....\n..x..\n.....", optics()).unwrap();
+        assert_eq!(
+            illuminate_board(&board, Coord(0, 0), Dir::East, None).energised(),
+            5 + 2
+        );
+
+        // The one-way mirror lets beams heading east through, and turns beams
+        // heading west to the south
+        let board = Board::with_optics(".>.\n...\n...", optics()).unwrap();
+        assert_eq!(
+            illuminate_board(&board, Coord(0, 0), Dir::East, None).energised(),
+            3
+        );
+        assert_eq!(
+            illuminate_board(&board, Coord(0, 2), Dir::West, None).energised(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_unknown_tile() {
+        let res = Board::with_optics("..?..", Optics::standard());
+
+        assert!(res.is_err());
+    }
+
     #[test]
     fn test_input() {
         let input = include_str!("../input.txt");
@@ -0,0 +1,161 @@
+//! A table of how each kind of tile redirects a beam, so that new optical
+//! elements can be described in a config file instead of in the simulator. See
+//! `optics.txt` for the format.
+
+use std::collections::HashMap;
+
+use crate::Dir;
+
+const ALL_DIRS: [Dir; 4] = [Dir::North, Dir::East, Dir::South, Dir::West];
+
+fn parse_dir(c: char) -> Result<Dir, String> {
+    match c {
+        'N' => Ok(Dir::North),
+        'E' => Ok(Dir::East),
+        'S' => Ok(Dir::South),
+        'W' => Ok(Dir::West),
+        _ => Err(format!("unknown direction {:?}", c)),
+    }
+}
+
+fn parse_dirs(dirs: &str) -> Result<Vec<Dir>, String> {
+    dirs.chars().map(parse_dir).collect()
+}
+
+enum Outgoing {
+    /// Carry on in the incoming direction
+    Same,
+    Dirs(Vec<Dir>),
+}
+
+/// Parse one line of the table into its tile, incoming directions and outgoing
+/// directions
+fn parse_entry(line: &str) -> Result<(char, Vec<Dir>, Outgoing), String> {
+    let [tile, incoming, outgoing] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+        return Err("expected `<tile> <incoming> <outgoing>`".to_string());
+    };
+
+    let mut tile_chars = tile.chars();
+    let (Some(tile), None) = (tile_chars.next(), tile_chars.next()) else {
+        return Err(format!("tile {:?} should be a single character", tile));
+    };
+
+    let incoming = match incoming {
+        "*" => ALL_DIRS.to_vec(),
+        dirs => parse_dirs(dirs)?,
+    };
+
+    let outgoing = match outgoing {
+        "same" => Outgoing::Same,
+        "none" => Outgoing::Dirs(vec![]),
+        dirs => Outgoing::Dirs(parse_dirs(dirs)?),
+    };
+
+    Ok((tile, incoming, outgoing))
+}
+
+pub struct Optics {
+    /// Outgoing directions for each tile and incoming direction
+    table: HashMap<(char, Dir), Vec<Dir>>,
+}
+
+impl Optics {
+    /// The mirrors and splitters from the puzzle
+    pub fn standard() -> Self {
+        Self::parse(include_str!("../optics.txt")).expect("Built-in optics table is valid")
+    }
+
+    pub fn parse(config: &str) -> Result<Self, String> {
+        let mut table = HashMap::new();
+
+        for (n, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            let (tile, incoming, outgoing) =
+                parse_entry(line).map_err(|err| format!("line {}: {}", n + 1, err))?;
+
+            for dir in incoming {
+                let out = match &outgoing {
+                    Outgoing::Same => vec![dir],
+                    Outgoing::Dirs(dirs) => dirs.clone(),
+                };
+
+                table.insert((tile, dir), out);
+            }
+        }
+
+        Ok(Self { table })
+    }
+
+    /// Check that every tile has a behaviour for beams coming from any direction
+    pub fn check(&self, tiles: impl Iterator<Item = char>) -> Result<(), String> {
+        for tile in tiles {
+            for dir in ALL_DIRS {
+                if !self.table.contains_key(&(tile, dir)) {
+                    return Err(format!(
+                        "no behaviour for tile {:?} with a beam heading {:?}",
+                        tile, dir
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Directions a beam leaves a tile in, given the direction it was heading.
+    /// Panics for tiles that weren't checked.
+    pub fn outgoing(&self, tile: char, dir: Dir) -> &[Dir] {
+        self.table
+            .get(&(tile, dir))
+            .unwrap_or_else(|| panic!("Unrecognized combination ({}, {:?})", tile, dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standard() {
+        let optics = Optics::standard();
+
+        assert_eq!(optics.outgoing('.', Dir::West), &[Dir::West]);
+        assert_eq!(optics.outgoing('\\', Dir::East), &[Dir::South]);
+        assert_eq!(optics.outgoing('|', Dir::West), &[Dir::North, Dir::South]);
+        assert!(optics.check(".\\/-|".chars()).is_ok());
+        assert!(optics.check("x".chars()).is_err());
+    }
+
+    #[test]
+    fn test_extended() {
+        let optics = Optics::parse(include_str!("../extended_optics.txt")).unwrap();
+
+        assert_eq!(optics.outgoing('x', Dir::North), &[]);
+        assert_eq!(optics.outgoing('>', Dir::East), &[Dir::East]);
+        assert_eq!(optics.outgoing('>', Dir::West), &[Dir::South]);
+        assert_eq!(
+            optics.outgoing('+', Dir::East),
+            &[Dir::East, Dir::North, Dir::South]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Optics::parse(". * same\n/ N").err(),
+            Some("line 2: expected `<tile> <incoming> <outgoing>`".to_string())
+        );
+        assert_eq!(
+            Optics::parse("ab N E").err(),
+            Some("line 1: tile \"ab\" should be a single character".to_string())
+        );
+        assert_eq!(
+            Optics::parse("/ Q E").err(),
+            Some("line 1: unknown direction 'Q'".to_string())
+        );
+    }
+}
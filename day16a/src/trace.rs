@@ -0,0 +1,147 @@
+//! Recording of every step a beam takes, for replaying or exporting afterwards.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::{Board, Coord, Dir};
+
+/// One step of a beam through the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Step {
+    coord: Coord,
+    dir: Dir,
+    /// Number of forked beams still waiting to be followed
+    queued: usize,
+}
+
+#[derive(Default)]
+pub struct Trace {
+    steps: Vec<Step>,
+}
+
+impl Trace {
+    pub fn record(&mut self, coord: Coord, dir: Dir, queued: usize) {
+        self.steps.push(Step { coord, dir, queued });
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Draw the board as it was at the given step. Empty tiles the beam has
+    /// already passed through are shown as `#`, and the beam itself by the
+    /// direction it's going.
+    fn render(&self, board: &Board, energised: &[Vec<bool>], step: usize) -> String {
+        let current = &self.steps[step];
+        let direction_symbol = match current.dir {
+            Dir::North => '^',
+            Dir::East => '>',
+            Dir::South => 'v',
+            Dir::West => '<',
+        };
+
+        let mut frame = String::new();
+        for (i, row) in board.inner.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                frame.push(if Coord(i as i32, j as i32) == current.coord {
+                    direction_symbol
+                } else if cell == '.' && energised[i][j] {
+                    '#'
+                } else {
+                    cell
+                });
+            }
+            frame.push('\n');
+        }
+
+        frame += &format!(
+            "Step {} / {}, queued beams: {}\n",
+            step,
+            self.steps.len() - 1,
+            current.queued
+        );
+
+        frame
+    }
+
+    /// Tiles energised by all the steps before the given one
+    fn energised_before(&self, board: &Board, step: usize) -> Vec<Vec<bool>> {
+        let mut energised: Vec<Vec<bool>> = board
+            .inner
+            .iter()
+            .map(|row| vec![false; row.len()])
+            .collect();
+
+        for s in &self.steps[..step] {
+            energised[s.coord.0 as usize][s.coord.1 as usize] = true;
+        }
+
+        energised
+    }
+
+    /// Draw a single step
+    pub fn frame(&self, board: &Board, step: usize) -> String {
+        let step = step.min(self.steps.len() - 1);
+        self.render(board, &self.energised_before(board, step), step)
+    }
+
+    /// Play the trace back in the terminal at `fps` frames per second, starting
+    /// from step `from`
+    pub fn play(&self, board: &Board, fps: f64, from: usize) {
+        let delay = Duration::from_secs_f64(1.0 / fps);
+        let from = from.min(self.steps.len());
+        let mut energised = self.energised_before(board, from);
+
+        // Clear the screen
+        print!("\x1B[2J");
+
+        for step in from..self.steps.len() {
+            // Move cursor to 0, 0
+            print!("\x1B[0;0H{}", self.render(board, &energised, step));
+            io::stdout().flush().unwrap();
+
+            let Coord(i, j) = self.steps[step].coord;
+            energised[i as usize][j as usize] = true;
+
+            thread::sleep(delay);
+        }
+    }
+
+    /// Write the trace out as CSV, one step per line
+    pub fn export(&self, path: &Path) -> io::Result<()> {
+        let mut out = String::from("step,row,col,dir,queued\n");
+
+        for (n, step) in self.steps.iter().enumerate() {
+            out += &format!(
+                "{},{},{},{:?},{}\n",
+                n, step.coord.0, step.coord.1, step.dir, step.queued
+            );
+        }
+
+        fs::write(path, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::illuminate_board;
+
+    #[test]
+    fn test_trace() {
+        let board = Board::from_input(include_str!("../example.txt"));
+        let mut trace = Trace::default();
+        illuminate_board(&board, Coord(0, 0), Dir::East, Some(&mut trace));
+
+        assert_eq!(trace.steps[0].coord, Coord(0, 0));
+        assert_eq!(trace.steps[1].coord, Coord(0, 1));
+
+        // The second step has the beam just past the start, which is now energised
+        let frame = trace.frame(&board, 1);
+        assert!(frame.starts_with("#>...\\"));
+        assert!(frame.ends_with(&format!("Step 1 / {}, queued beams: 0\n", trace.len() - 1)));
+    }
+}
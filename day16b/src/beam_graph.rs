@@ -0,0 +1,284 @@
+//! Beam propagation with the work shared between entry points.
+//!
+//! Between splits a beam's path is fixed, so the board is cut into segments
+//! that each run from a starting state up to the tile where the beam splits (or
+//! leaves the board). Segments that feed back into each other are condensed
+//! into strongly connected components, and then the tiles reachable from each
+//! component are gathered once, so any entry point can be answered by a lookup.
+
+use std::collections::{HashMap, HashSet};
+use std::thread;
+
+use crate::{Board, Coord, Dir};
+
+const DIRS: [Dir; 4] = [Dir::North, Dir::East, Dir::South, Dir::West];
+
+/// Fixed-size set of tile indices
+#[derive(Clone)]
+struct TileSet {
+    words: Vec<u64>,
+}
+
+impl TileSet {
+    fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    fn insert(&mut self, tile: usize) {
+        self.words[tile / 64] |= 1 << (tile % 64);
+    }
+
+    fn union_with(&mut self, other: &TileSet) {
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+}
+
+/// A stretch of beam that doesn't split
+struct Segment {
+    tiles: Vec<usize>,
+    /// Starting states of the beams this one splits into, if any
+    next: Vec<(Coord, Dir)>,
+}
+
+/// Follow a beam from its starting state until it splits, leaves the board,
+/// or comes back around on itself
+fn trace(board: &Board, (mut coord, mut dir): (Coord, Dir)) -> Segment {
+    let mut seen: HashSet<(Coord, Dir)> = HashSet::new();
+    let mut tiles = vec![];
+
+    while let Some(tile) = board.get(&coord) {
+        if !seen.insert((coord, dir)) {
+            break;
+        }
+        tiles.push(board.tile_index(&coord));
+
+        match board.optics.outgoing(tile, dir) {
+            [next_dir] => {
+                dir = *next_dir;
+                coord = coord + next_dir;
+            }
+            dirs => {
+                let next = dirs
+                    .iter()
+                    .map(|d| (coord + d, *d))
+                    .filter(|(c, _)| board.get(c).is_some())
+                    .collect();
+
+                return Segment { tiles, next };
+            }
+        }
+    }
+
+    Segment {
+        tiles,
+        next: vec![],
+    }
+}
+
+/// Map over items using all available threads
+pub fn parallel_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = items.len().div_ceil(threads).max(1);
+    let f = &f;
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Tarjan's algorithm, without recursion so that long chains of segments can't
+/// overflow the stack. Returns the component of each node and the number of
+/// components. Components are numbered in the order they complete, so each one
+/// only leads to components with smaller numbers.
+fn strongly_connected_components(edges: &[Vec<usize>]) -> (Vec<usize>, usize) {
+    const UNVISITED: usize = usize::MAX;
+
+    let n = edges.len();
+    let mut index = vec![UNVISITED; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut component = vec![UNVISITED; n];
+    let mut num_components = 0;
+    let mut next_index = 0;
+
+    for root in 0..n {
+        if index[root] != UNVISITED {
+            continue;
+        }
+
+        // Stand-in for the call stack, holding each node and its next edge to explore
+        let mut calls = vec![(root, 0)];
+        index[root] = next_index;
+        low[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some((node, edge)) = calls.last_mut() {
+            let node = *node;
+
+            if let Some(&next) = edges[node].get(*edge) {
+                *edge += 1;
+
+                if index[next] == UNVISITED {
+                    index[next] = next_index;
+                    low[next] = next_index;
+                    next_index += 1;
+                    stack.push(next);
+                    on_stack[next] = true;
+                    calls.push((next, 0));
+                } else if on_stack[next] {
+                    low[node] = low[node].min(index[next]);
+                }
+
+                continue;
+            }
+
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                low[parent] = low[parent].min(low[node]);
+            }
+
+            if low[node] == index[node] {
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack[member] = false;
+                    component[member] = num_components;
+
+                    if member == node {
+                        break;
+                    }
+                }
+
+                num_components += 1;
+            }
+        }
+    }
+
+    (component, num_components)
+}
+
+pub struct BeamGraph {
+    /// Segment starting at each state. Only states at the start of a segment
+    /// (entry points and just after splits) are included.
+    segments: HashMap<(Coord, Dir), usize>,
+    /// Strongly connected component of each segment
+    component: Vec<usize>,
+    /// Tiles energised by a beam starting anywhere in each component
+    reach: Vec<TileSet>,
+}
+
+impl BeamGraph {
+    /// Build the graph for beams entering at any of the given states
+    pub fn build(board: &Board, entries: &[(Coord, Dir)]) -> Self {
+        let (rows, cols) = board.size();
+
+        // Every segment starts either at an entry point or just after a split,
+        // so they can all be found up front and traced independently
+        let mut starts: Vec<(Coord, Dir)> = entries.to_vec();
+        for i in 0..rows {
+            for j in 0..cols {
+                let coord = Coord(i as i32, j as i32);
+                let tile = board.get(&coord).unwrap();
+
+                for dir in DIRS {
+                    let out = board.optics.outgoing(tile, dir);
+                    if out.len() > 1 {
+                        starts.extend(
+                            out.iter()
+                                .map(|d| (coord + d, *d))
+                                .filter(|(c, _)| board.get(c).is_some()),
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut segment_ids: HashMap<(Coord, Dir), usize> = HashMap::new();
+        starts.retain(|start| {
+            let next_id = segment_ids.len();
+            *segment_ids.entry(*start).or_insert(next_id) == next_id
+        });
+
+        let traced = parallel_map(&starts, |start| trace(board, *start));
+
+        let edges: Vec<Vec<usize>> = traced
+            .iter()
+            .map(|segment| segment.next.iter().map(|s| segment_ids[s]).collect())
+            .collect();
+
+        let (component, num_components) = strongly_connected_components(&edges);
+
+        let mut members = vec![vec![]; num_components];
+        for (segment, &c) in component.iter().enumerate() {
+            members[c].push(segment);
+        }
+
+        // Components only lead to lower-numbered ones, which are already done
+        let mut reach: Vec<TileSet> = Vec::with_capacity(num_components);
+        for (c, segments) in members.iter().enumerate() {
+            let mut tiles = TileSet::new(rows * cols);
+
+            for &segment in segments {
+                for &tile in &traced[segment].tiles {
+                    tiles.insert(tile);
+                }
+
+                for &next in &edges[segment] {
+                    if component[next] != c {
+                        tiles.union_with(&reach[component[next]]);
+                    }
+                }
+            }
+
+            reach.push(tiles);
+        }
+
+        Self {
+            segments: segment_ids,
+            component,
+            reach,
+        }
+    }
+
+    /// Number of tiles energised by a beam entering at one of the entry points
+    /// the graph was built with
+    pub fn energised(&self, start: (Coord, Dir)) -> usize {
+        let segment = self.segments[&start];
+        self.reach[self.component[segment]].len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strongly_connected_components() {
+        // 0 -> 1 <-> 2 -> 3, with 3 looping on itself
+        let edges = vec![vec![1], vec![2], vec![1, 3], vec![3]];
+        let (component, num_components) = strongly_connected_components(&edges);
+
+        assert_eq!(num_components, 3);
+        assert_eq!(component[1], component[2]);
+        assert!(component[3] < component[1]);
+        assert!(component[1] < component[0]);
+    }
+}
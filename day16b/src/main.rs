@@ -1,17 +1,36 @@
-use std::collections::HashSet;
+mod beam_graph;
+mod illumination;
+mod optics;
+mod trace;
+
 use std::ops::Add;
+use std::path::PathBuf;
+
+use beam_graph::{parallel_map, BeamGraph};
+use illumination::{Illumination, FIRST_BEAM};
+use optics::Optics;
+use trace::Trace;
 
 // There are a lot of similarities between these types and what we did in day 10
 
 struct Board {
     inner: Vec<Vec<char>>,
+    optics: Optics,
 }
 
 impl Board {
+    /// Build a board using the mirrors and splitters from the puzzle
     fn from_input(input: &str) -> Self {
-        Self {
-            inner: input.lines().map(|l| l.chars().collect()).collect(),
-        }
+        Self::with_optics(input, Optics::standard()).unwrap()
+    }
+
+    /// Build a board whose tiles behave according to the given table. Every tile
+    /// on the board must be in the table.
+    fn with_optics(input: &str, optics: Optics) -> Result<Self, String> {
+        let inner: Vec<Vec<char>> = input.lines().map(|l| l.chars().collect()).collect();
+        optics.check(inner.iter().flatten().copied())?;
+
+        Ok(Self { inner, optics })
     }
 
     /// Return the number of rows and columns in the board
@@ -19,6 +38,11 @@ impl Board {
         (self.inner.len(), self.inner[0].len())
     }
 
+    /// Index of a tile, counting along each row in turn
+    fn tile_index(&self, c: &Coord) -> usize {
+        c.0 as usize * self.inner[0].len() + c.1 as usize
+    }
+
     fn get(&self, c: &Coord) -> Option<char> {
         if c.0 < 0 || c.1 < 0 {
             return None;
@@ -29,30 +53,6 @@ impl Board {
             .and_then(|row| row.get(c.1 as usize))
             .cloned()
     }
-
-    #[cfg(feature = "interactive")]
-    fn print_at(&self, coord: &Coord, dir: &Dir) {
-        // Move cursor to 0, 0
-        print!("\x1B[0;0H");
-
-        let direction_symbol = match dir {
-            Dir::North => '^',
-            Dir::East => '>',
-            Dir::South => 'v',
-            Dir::West => '<',
-        };
-
-        for (i, row) in self.inner.iter().enumerate() {
-            for (j, cell) in row.iter().enumerate() {
-                if &Coord(i as i32, j as i32) == coord {
-                    print!("{}", direction_symbol)
-                } else {
-                    print!("{}", cell);
-                }
-            }
-            println!();
-        }
-    }
 }
 
 /// A (row, col) coordinate pair or vector. Using i32 so that we can subtract
@@ -90,112 +90,231 @@ impl Add<&Dir> for Coord {
     }
 }
 
-/// Wait for an enter press
-#[cfg(feature = "interactive")]
-fn wait() {
-    use std::io::stdin;
-    stdin().read_line(&mut String::new()).unwrap();
-}
-
-/// Send a beam through the board, returning the number of cells it illuminates
-fn illuminate_board(board: &Board, start: Coord, start_dir: Dir) -> usize {
-    // Set of coordinates and directions of illuminated cells, used to detect
-    // loops.
-    let mut illuminated: HashSet<(Coord, Dir)> = HashSet::new();
-
-    // Forked beams we have to keep track of, from their starting coordinate and direction
-    let mut beams: Vec<(Coord, Dir)> = vec![(start, start_dir)];
-
-    while let Some((mut coord, mut dir)) = beams.pop() {
+/// Send a beam through the board, returning everywhere it went. Every step the
+/// beam takes is recorded to `trace`, if given.
+fn illuminate_board(
+    board: &Board,
+    start: Coord,
+    start_dir: Dir,
+    mut trace: Option<&mut Trace>,
+) -> Illumination {
+    // Directions each cell has been illuminated in, also used to detect loops
+    let mut illumination = Illumination::new(board);
+
+    // Forked beams we have to keep track of, from their starting coordinate and
+    // direction, along with which beam they are
+    let mut beams: Vec<(Coord, Dir, usize)> = vec![(start, start_dir, FIRST_BEAM)];
+
+    while let Some((mut coord, mut dir, mut beam)) = beams.pop() {
         while let Some(char) = board.get(&coord) {
-            let key = (coord, dir);
-            if illuminated.contains(&key) {
+            if !illumination.visit(coord, dir, beam) {
                 break;
-            } else {
-                illuminated.insert(key);
             }
 
-            #[cfg(feature = "interactive")]
-            {
-                board.print_at(&coord, &dir);
-                println!("Beams: {:?}", beams);
-                wait();
+            if let Some(trace) = trace.as_mut() {
+                trace.record(coord, dir, beams.len());
             }
 
-            // dir is the direction we're going, not the direction we're coming from
-            dir = match (char, &dir) {
-                ('.', _) => dir,
-                ('/', Dir::North) => Dir::East,
-                ('/', Dir::East) => Dir::North,
-                ('/', Dir::South) => Dir::West,
-                ('/', Dir::West) => Dir::South,
-                ('\\', Dir::North) => Dir::West,
-                ('\\', Dir::East) => Dir::South,
-                ('\\', Dir::South) => Dir::East,
-                ('\\', Dir::West) => Dir::North,
-                ('-', Dir::East) | ('-', Dir::West) => dir,
-                ('-', Dir::North) | ('-', Dir::South) => {
-                    // Enqueue the west direction, proceed with east
-                    beams.push((&coord + &Dir::West, Dir::West));
-                    Dir::East
-                }
-                ('|', Dir::North) | ('|', Dir::South) => dir,
-                ('|', Dir::East) | ('|', Dir::West) => {
-                    // Enqueue the south direction, proceed with north
-                    beams.push((&coord + &Dir::South, Dir::South));
-                    Dir::North
-                }
-                (c, d) => panic!("Unrecognized combination ({}, {:?})", c, d),
+            // dir is the direction we're going, not the direction we're coming from.
+            // Continue with the first outgoing direction, and enqueue any others.
+            let Some((first, rest)) = board.optics.outgoing(char, dir).split_first() else {
+                // Absorbed
+                break;
             };
+            // Every way out of a splitter is a new beam, so that a beam's own
+            // path is just its and those of the beams it was split off from
+            if !rest.is_empty() {
+                illumination.record_split(coord);
+                for other in rest {
+                    beams.push((coord + other, *other, illumination.split_beam(beam)));
+                }
+                beam = illumination.split_beam(beam);
+            }
+            dir = *first;
 
             coord = coord + &dir;
         }
     }
 
-    illuminated
-        .iter()
-        .map(|(coord, _)| coord)
-        .collect::<HashSet<_>>()
-        .len()
+    illumination
 }
 
-fn solution(input: &str) -> usize {
-    let board = Board::from_input(input);
+/// Every state a beam can enter the board in, from each tile along the edges
+fn edge_entries(board: &Board) -> Vec<(Coord, Dir)> {
+    let (rows, cols) = board.size();
+    let (rows, cols) = (rows as i32, cols as i32);
+
+    (0..rows)
+        .flat_map(|i| [(Coord(i, 0), Dir::East), (Coord(i, cols - 1), Dir::West)])
+        .chain(
+            (0..cols).flat_map(|j| [(Coord(0, j), Dir::South), (Coord(rows - 1, j), Dir::North)]),
+        )
+        .collect()
+}
 
-    let mut scores: Vec<usize> = Vec::new();
+/// The entry point that energises the most tiles, and how many it energises
+fn best_entry(board: &Board) -> ((Coord, Dir), usize) {
+    let entries = edge_entries(board);
+    let graph = BeamGraph::build(board, &entries);
 
-    let (rows, cols) = board.size();
+    let energised = parallel_map(&entries, |&entry| graph.energised(entry));
 
-    for i in 0..rows {
-        scores.push(illuminate_board(&board, Coord(i as i32, 0), Dir::East));
-        scores.push(illuminate_board(
-            &board,
-            Coord(i as i32, cols as i32 - 1),
-            Dir::West,
-        ));
-    }
+    entries
+        .into_iter()
+        .zip(energised)
+        .max_by_key(|(_, energised)| *energised)
+        .unwrap()
+}
 
-    for j in 0..cols {
-        scores.push(illuminate_board(&board, Coord(0, j as i32), Dir::South));
-        scores.push(illuminate_board(
-            &board,
-            Coord(rows as i32 - 1, j as i32),
-            Dir::North,
-        ));
-    }
+fn solution(input: &str) -> usize {
+    best_entry(&Board::from_input(input)).1
+}
+
+/// Send a fresh beam in from every entry point, one at a time, returning the
+/// most tiles energised
+fn best_entry_naive(board: &Board) -> usize {
+    edge_entries(board)
+        .into_iter()
+        .map(|(start, dir)| illuminate_board(board, start, dir, None).energised())
+        .max()
+        .unwrap()
+}
+
+fn solution_naive(input: &str) -> usize {
+    best_entry_naive(&Board::from_input(input))
+}
 
-    *scores.iter().max().unwrap()
+/// Parse an entry point given as `ROW,COL,DIR`, where DIR is one of N, E, S or W
+fn parse_entry(entry: &str) -> Result<(Coord, Dir), String> {
+    let [row, col, dir] = entry.split(',').collect::<Vec<_>>()[..] else {
+        return Err(format!("Expected ROW,COL,DIR but got {:?}", entry));
+    };
+
+    let number = |n: &str| {
+        n.trim()
+            .parse()
+            .map_err(|_| format!("Invalid coordinate {:?}", n))
+    };
+    let dir = match dir.trim() {
+        "N" => Dir::North,
+        "E" => Dir::East,
+        "S" => Dir::South,
+        "W" => Dir::West,
+        other => return Err(format!("Invalid direction {:?}", other)),
+    };
+
+    Ok((Coord(number(row)?, number(col)?), dir))
 }
 
 fn main() {
-    // Clear the screen
-    #[cfg(feature = "interactive")]
-    print!("\x1B[2J");
+    // Usage: day16b [--optics FILE] [--naive] [--play] [--fps N] [--step N] [--export FILE]
+    //               [--heatmap] [--entry ROW,COL,DIR]
+    //
+    // --optics loads the tile behaviours from FILE, in the format of optics.txt.
+    // --naive simulates every entry point from scratch. The other flags trace the
+    // beam from the best entry point, or the one given by --entry (with DIR one
+    // of N, E, S or W): --play replays its path in the terminal at --fps frames
+    // per second (default 30), starting from --step. Without --play, --step just
+    // shows that one step. --export writes every step of the path to FILE as CSV.
+    // --heatmap shows the directions each tile was lit in, along with the
+    // splitters hit and where beams looped back onto their own path or merged
+    // into another's.
+    let mut naive = false;
+    let mut heatmap = false;
+    let mut entry = None;
+    let mut play = false;
+    let mut fps = 30.0;
+    let mut step = None;
+    let mut export: Option<PathBuf> = None;
+    let mut optics: Option<PathBuf> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("{} requires a value", arg))
+        };
+
+        match arg.as_str() {
+            "--naive" => naive = true,
+            "--play" => play = true,
+            "--heatmap" => heatmap = true,
+            "--entry" => {
+                entry = Some(parse_entry(&value()).unwrap_or_else(|err| panic!("{}", err)))
+            }
+            "--fps" => fps = value().parse().expect("--fps takes a number"),
+            "--step" => step = Some(value().parse().expect("--step takes a step number")),
+            "--export" => export = Some(value().into()),
+            "--optics" => optics = Some(value().into()),
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
 
     let input = include_str!("../input.txt");
-    let res = solution(input);
+    let recording = play || step.is_some() || export.is_some();
+    let tracing = recording || heatmap || entry.is_some();
+
+    if !tracing && optics.is_none() {
+        let res = if naive {
+            solution_naive(input)
+        } else {
+            solution(input)
+        };
+
+        println!("Result: {}", res);
+        return;
+    }
+
+    let optics = match optics {
+        Some(path) => {
+            let config = std::fs::read_to_string(path).expect("Failed to read optics table");
+            Optics::parse(&config).unwrap_or_else(|err| panic!("Invalid optics table: {}", err))
+        }
+        None => Optics::standard(),
+    };
+
+    let board = Board::with_optics(input, optics).unwrap_or_else(|err| panic!("{}", err));
+
+    if !tracing {
+        let res = if naive {
+            best_entry_naive(&board)
+        } else {
+            best_entry(&board).1
+        };
+
+        println!("Result: {}", res);
+        return;
+    }
+
+    let (start, dir) = entry.unwrap_or_else(|| best_entry(&board).0);
+    if board.get(&start).is_none() {
+        panic!("Entry point {:?} is outside the board", start);
+    }
+
+    let mut trace = Trace::default();
+    let res = illuminate_board(&board, start, dir, recording.then_some(&mut trace));
+
+    if let Some(path) = export {
+        trace.export(&path).expect("Failed to export trace");
+        println!("Exported {} steps to {}", trace.len(), path.display());
+    }
+
+    if play {
+        trace.play(&board, fps, step.unwrap_or(0));
+    } else if let Some(step) = step {
+        print!("{}", trace.frame(&board, step));
+    }
 
-    println!("Result: {}", res);
+    if heatmap {
+        print!("{}\n{}", res.heatmap(&board), res.report());
+    }
+
+    println!(
+        "Result: {} (entering at {:?} heading {:?})",
+        res.energised(),
+        start,
+        dir
+    );
 }
 
 #[cfg(test)]
@@ -210,6 +329,65 @@ mod tests {
         assert_eq!(res, 51);
     }
 
+    #[test]
+    fn test_graph_matches_naive() {
+        for input in [include_str!("../example.txt"), include_str!("../input.txt")] {
+            let board = Board::from_input(input);
+            let entries = edge_entries(&board);
+            let graph = BeamGraph::build(&board, &entries);
+
+            for (start, dir) in entries {
+                assert_eq!(
+                    graph.energised((start, dir)),
+                    illuminate_board(&board, start, dir, None).energised(),
+                    "Mismatch entering at {:?} heading {:?}",
+                    start,
+                    dir
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_graph_with_extended_optics() {
+        let optics = Optics::parse(include_str!("../extended_optics.txt")).unwrap();
+        let board = Board::with_optics(
+            ".|...x....\n|.-.\\..+..\n.....|-...\n....>|....\n.x........\n\
+             .........\\\n..../.+\\..\n.-.-/..|..\n.|....-|.\\\n..//.|....",
+            optics,
+        )
+        .unwrap();
+
+        let entries = edge_entries(&board);
+        let graph = BeamGraph::build(&board, &entries);
+
+        for (start, dir) in entries {
+            assert_eq!(
+                graph.energised((start, dir)),
+                illuminate_board(&board, start, dir, None).energised(),
+                "Mismatch entering at {:?} heading {:?}",
+                start,
+                dir
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        assert_eq!(parse_entry("0,3,S"), Ok((Coord(0, 3), Dir::South)));
+        assert!(parse_entry("0,3").is_err());
+        assert!(parse_entry("0,x,S").is_err());
+        assert!(parse_entry("0,3,Q").is_err());
+    }
+
+    #[test]
+    fn test_naive() {
+        let input = include_str!("../example.txt");
+        let res = solution_naive(input);
+
+        assert_eq!(res, 51);
+    }
+
     #[test]
     fn test_input() {
         let input = include_str!("../input.txt");
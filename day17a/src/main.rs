@@ -1,14 +1,26 @@
+mod path;
+
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap, HashSet};
 
+use path::Path;
+
 /// A (row, col) coordinate pair or vector. Using i32 so that we can subtract
 /// when needed, but only positive values are valid.
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
 struct Coord(i32, i32);
 
-impl From<Coord> for (i32, i32) {
-    fn from(value: Coord) -> Self {
-        (value.0, value.1)
+impl Coord {
+    /// The coordinate `steps` blocks away in the given direction
+    fn step(self, dir: Dir, steps: i32) -> Coord {
+        let Coord(y, x) = self;
+
+        match dir {
+            Dir::North => Coord(y - steps, x),
+            Dir::East => Coord(y, x + steps),
+            Dir::South => Coord(y + steps, x),
+            Dir::West => Coord(y, x - steps),
+        }
     }
 }
 
@@ -20,6 +32,17 @@ enum Dir {
     West,
 }
 
+impl Dir {
+    fn turn_right(self) -> Dir {
+        match self {
+            Dir::North => Dir::East,
+            Dir::East => Dir::South,
+            Dir::South => Dir::West,
+            Dir::West => Dir::North,
+        }
+    }
+}
+
 struct Board {
     inner: Vec<Vec<usize>>,
 }
@@ -81,13 +104,14 @@ impl PartialOrd for Visit {
     }
 }
 
-fn solution(input: &str) -> usize {
-    let board = Board::from_input(input);
-
+/// Find the path from the top left to the bottom right that loses the least heat
+fn find_path(board: &Board) -> Path {
     let (rows, cols) = board.size();
     let goal = Coord(rows as i32 - 1, cols as i32 - 1);
 
     let mut costs: HashMap<(Coord, Dir), usize> = HashMap::new();
+    // The state each one was most cheaply reached from, to trace the path back
+    let mut came_from: HashMap<(Coord, Dir), (Coord, Dir)> = HashMap::new();
     let mut visited: HashSet<(Coord, Dir)> = HashSet::new();
     let mut to_visit = BinaryHeap::new();
 
@@ -113,10 +137,14 @@ fn solution(input: &str) -> usize {
         }
 
         if coord == goal {
-            return heat_lost;
-        }
+            let mut stops = vec![(coord, direction)];
+            while let Some(&previous) = came_from.get(stops.last().unwrap()) {
+                stops.push(previous);
+            }
+            stops.reverse();
 
-        let (y, x) = coord.into();
+            return Path::from_stops(board, &stops);
+        }
 
         // This prevents us from going in the same direction or backwards
         for new_direction in match direction {
@@ -128,22 +156,18 @@ fn solution(input: &str) -> usize {
             // Rather than letting us travel straight as a separate iteration,
             // enqueue all of the legal straight moves after going in new_direction at once.
             for steps in 1..=3 {
-                let c = match new_direction {
-                    Dir::North => Coord(y - steps, x),
-                    Dir::East => Coord(y, x + steps),
-                    Dir::South => Coord(y + steps, x),
-                    Dir::West => Coord(y, x - steps),
-                };
+                let c = coord.step(new_direction, steps);
 
                 if let Some(extra_cost) = board.get(&c) {
                     new_cost += extra_cost;
 
                     let is_cheaper = costs
                         .get(&(c, new_direction))
-                        .map_or(true, |&current| new_cost < current);
+                        .is_none_or(|&current| new_cost < current);
 
                     if is_cheaper {
                         costs.insert((c, new_direction), new_cost);
+                        came_from.insert((c, new_direction), (coord, direction));
                         to_visit.push(Visit {
                             coord: c,
                             heat_lost: new_cost,
@@ -158,11 +182,38 @@ fn solution(input: &str) -> usize {
     panic!("Could not find target node")
 }
 
+fn solution(input: &str) -> usize {
+    find_path(&Board::from_input(input)).heat_lost
+}
+
 fn main() {
+    // Usage: day17a [--path]
+    //
+    // --path draws the route taken over the heat loss grid, and lists each
+    // straight run with the heat lost along it.
+    let mut show_path = false;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--path" => show_path = true,
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
+
     let input = include_str!("../input.txt");
-    let res = solution(input);
 
-    println!("Result: {}", res);
+    if !show_path {
+        let res = solution(input);
+
+        println!("Result: {}", res);
+        return;
+    }
+
+    let board = Board::from_input(input);
+    let path = find_path(&board);
+
+    println!("{}\n{}", path.render(&board), path);
+    println!("Result: {}", path.heat_lost);
 }
 
 #[cfg(test)]
@@ -177,6 +228,36 @@ mod tests {
         assert_eq!(res, 102);
     }
 
+    #[test]
+    fn test_example_path() {
+        let board = Board::from_input(include_str!("../example.txt"));
+        let path = find_path(&board);
+
+        assert_eq!(path.heat_lost, 102);
+        assert!(path.segments.iter().all(|s| (1..=3).contains(&s.length)));
+
+        // Runs join up, and are all separated by turns
+        for pair in path.segments.windows(2) {
+            assert_eq!(pair[0].end(), pair[1].start);
+        }
+        assert_eq!(path.turns().len(), path.segments.len() - 1);
+
+        let coords = path.coords();
+        assert_eq!(coords.first(), Some(&Coord(0, 0)));
+        assert_eq!(coords.last(), Some(&Coord(12, 12)));
+        let heat: usize = coords[1..].iter().map(|c| board.get(c).unwrap()).sum();
+        assert_eq!(heat, 102);
+    }
+
+    #[test]
+    fn test_empty_path() {
+        let board = Board::from_input("5");
+        let path = Path::from_stops(&board, &[(Coord(0, 0), Dir::East)]);
+
+        assert!(path.segments.is_empty());
+        assert_eq!(path.to_string(), "Total heat lost: 0 over 0 blocks");
+    }
+
     #[test]
     fn test_input() {
         let input = include_str!("../input.txt");
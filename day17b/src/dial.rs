@@ -0,0 +1,222 @@
+//! A* search over a bucketed priority queue, with all state in flat arrays.
+//!
+//! Heat losses are small integers, so instead of a binary heap the frontier is
+//! kept in Dial's bucket queue: one bucket per priority, in a ring just wide
+//! enough to hold every priority that can be waiting at once. The crucible only
+//! ever turns, so the direction it's heading doesn't matter beyond which axis
+//! it's on, and each state is a block plus an axis.
+
+use std::cmp::Ordering;
+
+use crate::path::Path;
+use crate::{Board, Coord, Dir};
+
+const UNREACHED: usize = usize::MAX;
+
+/// Priority queue for small integer priorities that never go below the last
+/// one popped
+pub struct BucketQueue {
+    buckets: Vec<Vec<usize>>,
+    /// Priority of the bucket currently being emptied
+    current: usize,
+    len: usize,
+}
+
+impl BucketQueue {
+    /// Make a queue for priorities from `lowest` up, at most `spread` above
+    /// the last one popped. Starting from `lowest` rather than 0 matters, as
+    /// the first priority can be far more than `spread`.
+    pub fn new(lowest: usize, spread: usize) -> Self {
+        Self {
+            buckets: vec![vec![]; spread + 1],
+            current: lowest,
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, priority: usize, item: usize) {
+        debug_assert!(priority >= self.current && priority - self.current < self.buckets.len());
+
+        let slot = priority % self.buckets.len();
+        self.buckets[slot].push(item);
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<(usize, usize)> {
+        if self.len == 0 {
+            return None;
+        }
+
+        loop {
+            let slot = self.current % self.buckets.len();
+            if let Some(item) = self.buckets[slot].pop() {
+                self.len -= 1;
+                return Some((self.current, item));
+            }
+            self.current += 1;
+        }
+    }
+}
+
+/// Find the path from the top left to the bottom right that loses the least
+/// heat. Gives the same answer as `find_path`.
+pub fn find_path(board: &Board, min_straight: usize, max_straight: usize) -> Path {
+    let (rows, cols) = board.size();
+    let heat: Vec<usize> = board.inner.concat();
+    let goal = rows * cols - 1;
+
+    // Every block entered loses at least this much heat, so this many per block
+    // of Manhattan distance never overestimates what's left. It's also
+    // consistent, so each state is final the first time it's popped.
+    let min_heat = heat.iter().copied().min().unwrap();
+    let estimate = |cell: usize| ((rows - 1 - cell / cols) + (cols - 1 - cell % cols)) * min_heat;
+
+    // A move of n blocks raises the priority by its heat loss, minus however
+    // much closer it gets
+    let max_heat = heat.iter().copied().max().unwrap();
+    let mut queue = BucketQueue::new(estimate(0), max_straight * (max_heat + min_heat));
+
+    // Indexed by block * 2 + axis, where axis 0 is north/south and 1 east/west
+    let mut costs = vec![UNREACHED; rows * cols * 2];
+    let mut came_from = vec![UNREACHED; rows * cols * 2];
+    let mut done = vec![false; rows * cols * 2];
+
+    // We could set off along either axis from the starting cell
+    for start in [0, 1] {
+        costs[start] = 0;
+        queue.push(estimate(0), start);
+    }
+
+    while let Some((_, state)) = queue.pop() {
+        if done[state] {
+            continue;
+        }
+        done[state] = true;
+
+        let (cell, axis) = (state / 2, state % 2);
+        if cell == goal {
+            return trace_back(board, &came_from, state);
+        }
+
+        let (y, x) = (cell / cols, cell % cols);
+        let new_axis = 1 - axis;
+
+        // Going straight on is handled by enqueuing every run length at once,
+        // so only turn onto the other axis, both ways
+        for sign in [-1, 1] {
+            let mut new_cost = costs[state];
+
+            for steps in 1..=max_straight as isize {
+                let (ny, nx) = if new_axis == 0 {
+                    (y as isize + sign * steps, x as isize)
+                } else {
+                    (y as isize, x as isize + sign * steps)
+                };
+                if ny < 0 || nx < 0 || ny >= rows as isize || nx >= cols as isize {
+                    break;
+                }
+
+                let new_cell = ny as usize * cols + nx as usize;
+                new_cost += heat[new_cell];
+
+                if (steps as usize) < min_straight {
+                    continue;
+                }
+
+                let next = new_cell * 2 + new_axis;
+                if new_cost < costs[next] {
+                    costs[next] = new_cost;
+                    came_from[next] = state;
+                    queue.push(new_cost + estimate(new_cell), next);
+                }
+            }
+        }
+    }
+
+    panic!("Could not find target node")
+}
+
+/// Follow the links back from the goal state to build the path
+fn trace_back(board: &Board, came_from: &[usize], goal: usize) -> Path {
+    let cols = board.size().1;
+    let coord = |state: usize| Coord((state / 2 / cols) as i32, (state / 2 % cols) as i32);
+
+    let mut states = vec![goal];
+    while came_from[*states.last().unwrap()] != UNREACHED {
+        states.push(came_from[*states.last().unwrap()]);
+    }
+    states.reverse();
+
+    // Work out which way each run went from where it started and stopped.
+    // The start has no run into it, so its direction isn't used.
+    let mut stops = vec![(coord(states[0]), Dir::East)];
+    for pair in states.windows(2) {
+        let (Coord(y0, x0), Coord(y1, x1)) = (coord(pair[0]), coord(pair[1]));
+        let dir = match (y1.cmp(&y0), x1.cmp(&x0)) {
+            (Ordering::Less, _) => Dir::North,
+            (Ordering::Greater, _) => Dir::South,
+            (_, Ordering::Less) => Dir::West,
+            _ => Dir::East,
+        };
+        stops.push((Coord(y1, x1), dir));
+    }
+
+    Path::from_stops(board, &stops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_queue() {
+        let mut queue = BucketQueue::new(0, 3);
+        queue.push(2, 20);
+        queue.push(0, 0);
+        queue.push(3, 30);
+
+        assert_eq!(queue.pop(), Some((0, 0)));
+        assert_eq!(queue.pop(), Some((2, 20)));
+
+        // Wraps round into a slot already passed
+        queue.push(5, 50);
+        queue.push(2, 21);
+
+        let mut rest = vec![];
+        while let Some(entry) = queue.pop() {
+            rest.push(entry);
+        }
+        assert_eq!(rest, vec![(2, 21), (3, 30), (5, 50)]);
+    }
+
+    #[test]
+    fn test_bucket_queue_starts_high() {
+        // Far more than the spread, so the first priority isn't in the first
+        // ring round
+        let mut queue = BucketQueue::new(280, 3);
+        queue.push(283, 3);
+        queue.push(280, 0);
+        queue.push(281, 1);
+
+        assert_eq!(queue.pop(), Some((280, 0)));
+        queue.push(282, 2);
+        let mut rest = vec![];
+        while let Some(entry) = queue.pop() {
+            rest.push(entry);
+        }
+        assert_eq!(rest, vec![(281, 1), (282, 2), (283, 3)]);
+    }
+
+    #[test]
+    fn test_start_estimate_above_spread() {
+        // Every block loses 1, so the start's estimate of 38 is well above the
+        // spread of 2 * 3 = 6, and any path without detours is the best
+        let board = Board::from_input(&["11111111111111111111\n"; 20].concat());
+
+        assert_eq!(find_path(&board, 1, 3).heat_lost, 38);
+        assert_eq!(
+            find_path(&board, 1, 3).heat_lost,
+            crate::find_path(&board, 1, 3).heat_lost
+        );
+    }
+}
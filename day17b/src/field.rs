@@ -0,0 +1,125 @@
+//! The least heat lost getting from the start to every block on the board.
+
+use std::fmt::Write;
+
+use crate::rules::{cheapest_to_goals, CrucibleRules};
+use crate::{Board, Coord};
+
+/// 256-colour backgrounds from cheapest (blue) to dearest (red)
+const RAMP: [u8; 16] = [
+    21, 27, 33, 39, 45, 51, 49, 47, 46, 118, 190, 226, 220, 214, 208, 196,
+];
+
+pub struct DistanceField {
+    /// Least heat lost getting to each block, or None if it can't be reached
+    costs: Vec<Vec<Option<usize>>>,
+}
+
+impl DistanceField {
+    /// Run the search from the top left until every block has been settled, for
+    /// a crucible with the given limits on how far it moves in a straight line
+    pub fn from_origin(board: &Board, min_straight: usize, max_straight: usize) -> Self {
+        let (rows, cols) = board.size();
+        let rules = CrucibleRules {
+            goals: (0..rows as i32)
+                .flat_map(|i| (0..cols as i32).map(move |j| Coord(i, j)))
+                .collect(),
+            ..CrucibleRules::new(board, min_straight, max_straight)
+        };
+
+        let costs = cheapest_to_goals(board, &rules).unwrap_or_else(|err| panic!("{}", err));
+
+        Self {
+            costs: costs.chunks(cols).map(<[_]>::to_vec).collect(),
+        }
+    }
+
+    pub fn get(&self, c: &Coord) -> Option<usize> {
+        self.costs[c.0 as usize][c.1 as usize]
+    }
+
+    /// One line per block, leaving the heat lost empty for unreachable blocks
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("row,col,heat_lost\n");
+
+        for (i, row) in self.costs.iter().enumerate() {
+            for (j, cost) in row.iter().enumerate() {
+                let cost = cost.map(|c| c.to_string()).unwrap_or_default();
+                writeln!(csv, "{},{},{}", i, j, cost).unwrap();
+            }
+        }
+
+        csv
+    }
+
+    /// Draw the field with each block coloured by how much heat is lost getting
+    /// there. Unreachable blocks are left uncoloured and marked with `.`.
+    pub fn heatmap(&self) -> String {
+        let max = self
+            .costs
+            .iter()
+            .flatten()
+            .flatten()
+            .max()
+            .copied()
+            .unwrap_or(0);
+        let mut heatmap = String::new();
+
+        for row in &self.costs {
+            for cost in row {
+                match cost {
+                    Some(cost) => {
+                        let shade = (cost * (RAMP.len() - 1)).checked_div(max).unwrap_or(0);
+                        write!(heatmap, "\x1B[48;5;{}m  ", RAMP[shade]).unwrap();
+                    }
+                    None => heatmap += "\x1B[0m. ",
+                }
+            }
+            heatmap += "\x1B[0m\n";
+        }
+
+        heatmap += "0 ";
+        for colour in RAMP {
+            write!(heatmap, "\x1B[48;5;{}m  ", colour).unwrap();
+        }
+        writeln!(heatmap, "\x1B[0m {}", max).unwrap();
+
+        heatmap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_field() {
+        let board = Board::from_input(include_str!("../example.txt"));
+        let field = DistanceField::from_origin(&board, 4, 10);
+
+        assert_eq!(field.get(&Coord(0, 0)), Some(0));
+        assert_eq!(field.get(&Coord(0, 4)), Some(4 + 1 + 3 + 4));
+        assert_eq!(field.get(&Coord(12, 12)), Some(94));
+
+        let csv = field.to_csv();
+        assert_eq!(csv.lines().count(), 1 + 13 * 13);
+        assert!(csv.contains("\n0,4,12\n"));
+
+        let heatmap = field.heatmap();
+        assert_eq!(heatmap.lines().count(), 13 + 1);
+        assert!(heatmap.starts_with("\x1B[48;5;21m  "));
+    }
+
+    #[test]
+    fn test_unreachable_blocks() {
+        // Runs of exactly 2 from the corner of a single row never land on an odd
+        // column
+        let board = Board::from_input("11111");
+        let field = DistanceField::from_origin(&board, 2, 2);
+
+        assert_eq!(field.get(&Coord(0, 1)), None);
+        assert_eq!(field.get(&Coord(0, 2)), Some(2));
+        assert!(field.to_csv().contains("\n0,1,\n"));
+        assert!(field.heatmap().contains("\x1B[0m. "));
+    }
+}
@@ -1,14 +1,32 @@
+mod dial;
+mod field;
+mod path;
+mod rules;
+
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::PathBuf;
+
+use field::DistanceField;
+use path::Path;
+use rules::{cheapest_to_goals, CrucibleRules};
 
 /// A (row, col) coordinate pair or vector. Using i32 so that we can subtract
 /// when needed, but only positive values are valid.
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
 struct Coord(i32, i32);
 
-impl From<Coord> for (i32, i32) {
-    fn from(value: Coord) -> Self {
-        (value.0, value.1)
+impl Coord {
+    /// The coordinate `steps` blocks away in the given direction
+    fn step(self, dir: Dir, steps: i32) -> Coord {
+        let Coord(y, x) = self;
+
+        match dir {
+            Dir::North => Coord(y - steps, x),
+            Dir::East => Coord(y, x + steps),
+            Dir::South => Coord(y + steps, x),
+            Dir::West => Coord(y, x - steps),
+        }
     }
 }
 
@@ -20,6 +38,17 @@ enum Dir {
     West,
 }
 
+impl Dir {
+    fn turn_right(self) -> Dir {
+        match self {
+            Dir::North => Dir::East,
+            Dir::East => Dir::South,
+            Dir::South => Dir::West,
+            Dir::West => Dir::North,
+        }
+    }
+}
+
 struct Board {
     inner: Vec<Vec<usize>>,
 }
@@ -81,13 +110,22 @@ impl PartialOrd for Visit {
     }
 }
 
-fn solution(input: &str, min_straight: usize, max_straight: usize) -> usize {
-    let board = Board::from_input(input);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Solver {
+    /// Dijkstra's algorithm on a binary heap, with hashed state
+    Heap,
+    /// A* on a bucket queue, with state in flat arrays
+    Dial,
+}
 
+/// Find the path from the top left to the bottom right that loses the least heat
+fn find_path(board: &Board, min_straight: usize, max_straight: usize) -> Path {
     let (rows, cols) = board.size();
     let goal = Coord(rows as i32 - 1, cols as i32 - 1);
 
     let mut costs: HashMap<(Coord, Dir), usize> = HashMap::new();
+    // The state each one was most cheaply reached from, to trace the path back
+    let mut came_from: HashMap<(Coord, Dir), (Coord, Dir)> = HashMap::new();
     let mut visited: HashSet<(Coord, Dir)> = HashSet::new();
     let mut to_visit = BinaryHeap::new();
 
@@ -113,10 +151,14 @@ fn solution(input: &str, min_straight: usize, max_straight: usize) -> usize {
         }
 
         if coord == goal {
-            return heat_lost;
-        }
+            let mut stops = vec![(coord, direction)];
+            while let Some(&previous) = came_from.get(stops.last().unwrap()) {
+                stops.push(previous);
+            }
+            stops.reverse();
 
-        let (y, x) = coord.into();
+            return Path::from_stops(board, &stops);
+        }
 
         // This prevents us from going in the same direction or backwards
         for new_direction in match direction {
@@ -130,28 +172,23 @@ fn solution(input: &str, min_straight: usize, max_straight: usize) -> usize {
             // Part 2: We still have to go through (1..min_straight) to count the cost
             // of those cells, but we won't allow enqueuing from there.
             for steps in 1..=max_straight {
-                let steps = steps as i32; // Since we use i32 for coords
-
-                let c = match new_direction {
-                    Dir::North => Coord(y - steps, x),
-                    Dir::East => Coord(y, x + steps),
-                    Dir::South => Coord(y + steps, x),
-                    Dir::West => Coord(y, x - steps),
-                };
+                // Since we use i32 for coords
+                let c = coord.step(new_direction, steps as i32);
 
                 if let Some(extra_cost) = board.get(&c) {
                     new_cost += extra_cost;
 
-                    if (steps as usize) < min_straight {
+                    if steps < min_straight {
                         continue;
                     }
 
                     let is_cheaper = costs
                         .get(&(c, new_direction))
-                        .map_or(true, |&current| new_cost < current);
+                        .is_none_or(|&current| new_cost < current);
 
                     if is_cheaper {
                         costs.insert((c, new_direction), new_cost);
+                        came_from.insert((c, new_direction), (coord, direction));
                         to_visit.push(Visit {
                             coord: c,
                             heat_lost: new_cost,
@@ -166,11 +203,178 @@ fn solution(input: &str, min_straight: usize, max_straight: usize) -> usize {
     panic!("Could not find target node")
 }
 
+fn solve(board: &Board, min_straight: usize, max_straight: usize, solver: Solver) -> Path {
+    match solver {
+        Solver::Heap => find_path(board, min_straight, max_straight),
+        Solver::Dial => dial::find_path(board, min_straight, max_straight),
+    }
+}
+
+fn solution(input: &str, min_straight: usize, max_straight: usize) -> usize {
+    find_path(&Board::from_input(input), min_straight, max_straight).heat_lost
+}
+
+/// Parse a block given as `ROW,COL`
+fn parse_coord(coord: &str) -> Result<Coord, String> {
+    let (row, col) = coord
+        .split_once(',')
+        .ok_or_else(|| format!("Expected ROW,COL but got {:?}", coord))?;
+
+    let number = |n: &str| {
+        n.trim()
+            .parse()
+            .map_err(|_| format!("Invalid coordinate {:?}", n))
+    };
+
+    Ok(Coord(number(row)?, number(col)?))
+}
+
 fn main() {
+    // Usage: day17b [--solver heap|dial] [--path]
+    //        day17b rules [--min N] [--max N] [--turn-penalty N] [--u-turns]
+    //                     [--stop-anywhere] [--start ROW,COL]... [--goal ROW,COL]...
+    //        day17b field [--min N] [--max N] [--csv FILE]
+    //
+    // --solver picks between Dijkstra on a binary heap (the default) and A* on
+    // a bucket queue. --path draws the route taken over the heat loss grid, and
+    // lists each straight run with the heat lost along it.
+    //
+    // rules finds the least heat lost getting to each goal under the given
+    // crucible rules. These default to the puzzle's: 4 to 10 blocks in a line, no
+    // turn penalty or U-turns, only stopping after at least 4 blocks, and going
+    // from the top left to the bottom right. Giving any --start or --goal
+    // replaces the defaults, and each can be given more than once.
+    //
+    // field shows the least heat lost getting from the top left to every block
+    // as a heatmap, for a crucible moving --min to --max blocks in a line
+    // (default 4 to 10). --csv also writes it to FILE.
     let input = include_str!("../input.txt");
-    let res = solution(input, 4, 10);
 
-    println!("Result: {}", res);
+    if std::env::args().nth(1).as_deref() == Some("field") {
+        let (mut min_straight, mut max_straight) = (4, 10);
+        let mut csv: Option<PathBuf> = None;
+
+        let mut args = std::env::args().skip(2);
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .unwrap_or_else(|| panic!("{} requires a value", arg))
+            };
+
+            match arg.as_str() {
+                "--min" => min_straight = value().parse().expect("--min takes a number"),
+                "--max" => max_straight = value().parse().expect("--max takes a number"),
+                "--csv" => csv = Some(value().into()),
+                _ => panic!("Unknown argument {}", arg),
+            }
+        }
+
+        let board = Board::from_input(input);
+        let field = DistanceField::from_origin(&board, min_straight, max_straight);
+        print!("{}", field.heatmap());
+
+        if let Some(path) = csv {
+            std::fs::write(&path, field.to_csv()).expect("Failed to write CSV");
+            println!("Wrote {}", path.display());
+        }
+
+        let (rows, cols) = board.size();
+        match field.get(&Coord(rows as i32 - 1, cols as i32 - 1)) {
+            Some(res) => println!("Result: {}", res),
+            None => println!("Bottom right is unreachable"),
+        }
+        return;
+    }
+
+    if std::env::args().nth(1).as_deref() == Some("rules") {
+        let board = Board::from_input(input);
+        let mut rules = CrucibleRules::new(&board, 4, 10);
+        let mut starts = vec![];
+        let mut goals = vec![];
+
+        let mut args = std::env::args().skip(2);
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .unwrap_or_else(|| panic!("{} requires a value", arg))
+            };
+            let mut number = || value().parse().expect("Expected a number");
+
+            match arg.as_str() {
+                "--min" => rules.min_straight = number(),
+                "--max" => rules.max_straight = number(),
+                "--turn-penalty" => rules.turn_penalty = number(),
+                "--u-turns" => rules.u_turns = true,
+                "--stop-anywhere" => rules.stop_after_min_straight = false,
+                "--start" | "--goal" => {
+                    let coord = parse_coord(&value()).unwrap_or_else(|err| panic!("{}", err));
+                    if arg == "--start" {
+                        starts.push(coord);
+                    } else {
+                        goals.push(coord);
+                    }
+                }
+                _ => panic!("Unknown argument {}", arg),
+            }
+        }
+
+        if !starts.is_empty() {
+            rules.starts = starts;
+        }
+        if !goals.is_empty() {
+            rules.goals = goals;
+        }
+
+        let costs = cheapest_to_goals(&board, &rules).unwrap_or_else(|err| panic!("{}", err));
+        for (Coord(i, j), cost) in rules.goals.iter().zip(costs) {
+            match cost {
+                Some(cost) => println!("({}, {}): {}", i, j, cost),
+                None => println!("({}, {}): unreachable", i, j),
+            }
+        }
+        return;
+    }
+
+    let mut show_path = false;
+    let mut solver = Solver::Heap;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("{} requires a value", arg))
+        };
+
+        match arg.as_str() {
+            "--path" => show_path = true,
+            "--solver" => {
+                solver = match value().as_str() {
+                    "heap" => Solver::Heap,
+                    "dial" => Solver::Dial,
+                    other => panic!("Unknown solver {}", other),
+                }
+            }
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
+
+    if !show_path && solver == Solver::Heap {
+        let res = solution(input, 4, 10);
+
+        println!("Result: {}", res);
+        return;
+    }
+
+    let board = Board::from_input(input);
+    let path = solve(&board, 4, 10, solver);
+
+    if !show_path {
+        println!("Result: {}", path.heat_lost);
+        return;
+    }
+
+    println!("{}\n{}", path.render(&board), path);
+    println!("Result: {}", path.heat_lost);
 }
 
 #[cfg(test)]
@@ -185,6 +389,88 @@ mod tests {
         assert_eq!(res, 94);
     }
 
+    #[test]
+    fn test_example_path() {
+        let board = Board::from_input(include_str!("../example.txt"));
+        let path = find_path(&board, 4, 10);
+
+        assert_eq!(path.heat_lost, 94);
+        assert!(path.segments.iter().all(|s| (4..=10).contains(&s.length)));
+
+        // Runs join up, and are all separated by turns
+        for pair in path.segments.windows(2) {
+            assert_eq!(pair[0].end(), pair[1].start);
+        }
+        assert_eq!(path.turns().len(), path.segments.len() - 1);
+
+        let coords = path.coords();
+        assert_eq!(coords.first(), Some(&Coord(0, 0)));
+        assert_eq!(coords.last(), Some(&Coord(12, 12)));
+        let heat: usize = coords[1..].iter().map(|c| board.get(c).unwrap()).sum();
+        assert_eq!(heat, 94);
+
+        // Matches the route shown in the puzzle
+        assert_eq!(path.render(&board).lines().next(), Some("2>>>>>>>>1323"));
+    }
+
+    #[test]
+    fn test_empty_path() {
+        let board = Board::from_input("5");
+        let path = Path::from_stops(&board, &[(Coord(0, 0), Dir::East)]);
+
+        assert!(path.segments.is_empty());
+        assert_eq!(path.to_string(), "Total heat lost: 0 over 0 blocks");
+    }
+
+    #[test]
+    fn test_dial_matches_heap() {
+        let inputs = [
+            include_str!("../example.txt"),
+            "111111111111\n999999999991\n999999999991\n999999999991\n999999999991",
+        ];
+
+        for input in inputs {
+            let board = Board::from_input(input);
+
+            for (min_straight, max_straight) in [(1, 3), (4, 10)] {
+                let heap = solve(&board, min_straight, max_straight, Solver::Heap);
+                let dial = solve(&board, min_straight, max_straight, Solver::Dial);
+
+                assert_eq!(dial.heat_lost, heap.heat_lost);
+                assert!(dial
+                    .segments
+                    .iter()
+                    .all(|s| (min_straight..=max_straight).contains(&s.length)));
+            }
+        }
+
+        let board = Board::from_input(inputs[1]);
+        assert_eq!(solve(&board, 4, 10, Solver::Dial).heat_lost, 71);
+    }
+
+    #[test]
+    fn test_dial_input() {
+        let board = Board::from_input(include_str!("../input.txt"));
+        let res = solve(&board, 4, 10, Solver::Dial);
+
+        assert_eq!(res.heat_lost, 1268);
+    }
+
+    #[test]
+    fn test_rules_input() {
+        let board = Board::from_input(include_str!("../input.txt"));
+        let rules = CrucibleRules::new(&board, 4, 10);
+
+        assert_eq!(cheapest_to_goals(&board, &rules), Ok(vec![Some(1268)]));
+    }
+
+    #[test]
+    fn test_parse_coord() {
+        assert_eq!(parse_coord("3, 12"), Ok(Coord(3, 12)));
+        assert!(parse_coord("3").is_err());
+        assert!(parse_coord("3,x").is_err());
+    }
+
     #[test]
     fn test_input() {
         let input = include_str!("../input.txt");
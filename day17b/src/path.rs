@@ -0,0 +1,156 @@
+//! The route the crucible takes, for showing and checking the solver's answer.
+
+use std::fmt;
+
+use crate::{Board, Coord, Dir};
+
+/// Which way the crucible turns between two straight runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    Left,
+    Right,
+}
+
+/// A straight run of the path, after turning to face `dir`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub start: Coord,
+    pub dir: Dir,
+    pub length: usize,
+    /// Heat lost entering each block of the run
+    pub heat_lost: usize,
+}
+
+impl Segment {
+    /// The blocks entered along the run, not including the one it starts from
+    pub fn coords(&self) -> impl Iterator<Item = Coord> + '_ {
+        (1..=self.length as i32).map(|steps| self.start.step(self.dir, steps))
+    }
+
+    pub fn end(&self) -> Coord {
+        self.start.step(self.dir, self.length as i32)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    pub segments: Vec<Segment>,
+    pub heat_lost: usize,
+}
+
+impl Path {
+    /// Build the path through a list of states, each the block the crucible
+    /// stopped at and the direction it was heading in to get there. The first
+    /// state is the start.
+    pub fn from_stops(board: &Board, stops: &[(Coord, Dir)]) -> Self {
+        let segments: Vec<Segment> = stops
+            .windows(2)
+            .map(|pair| {
+                let (start, _) = pair[0];
+                let (end, dir) = pair[1];
+                let length = (start.0.abs_diff(end.0) + start.1.abs_diff(end.1)) as usize;
+
+                let mut segment = Segment {
+                    start,
+                    dir,
+                    length,
+                    heat_lost: 0,
+                };
+                segment.heat_lost = segment.coords().map(|c| board.get(&c).unwrap()).sum();
+                segment
+            })
+            .collect();
+
+        let heat_lost = segments.iter().map(|s| s.heat_lost).sum();
+
+        Self {
+            segments,
+            heat_lost,
+        }
+    }
+
+    /// Every block along the path, including the start
+    pub fn coords(&self) -> Vec<Coord> {
+        let mut coords = vec![];
+        if let Some(first) = self.segments.first() {
+            coords.push(first.start);
+        }
+        for segment in &self.segments {
+            coords.extend(segment.coords());
+        }
+        coords
+    }
+
+    /// Where the path turns between straight runs, and which way
+    pub fn turns(&self) -> Vec<(Coord, Turn)> {
+        self.segments
+            .windows(2)
+            .map(|pair| {
+                let turn = if pair[0].dir.turn_right() == pair[1].dir {
+                    Turn::Right
+                } else {
+                    Turn::Left
+                };
+                (pair[0].end(), turn)
+            })
+            .collect()
+    }
+
+    /// Draw the heat loss grid with the path over it as arrows
+    pub fn render(&self, board: &Board) -> String {
+        let mut grid: Vec<Vec<char>> = board
+            .inner
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&heat| char::from_digit(heat as u32, 10).unwrap())
+                    .collect()
+            })
+            .collect();
+
+        for segment in &self.segments {
+            let arrow = match segment.dir {
+                Dir::North => '^',
+                Dir::East => '>',
+                Dir::South => 'v',
+                Dir::West => '<',
+            };
+
+            for Coord(i, j) in segment.coords() {
+                grid[i as usize][j as usize] = arrow;
+            }
+        }
+
+        grid.into_iter()
+            .map(|row| row.into_iter().collect::<String>() + "\n")
+            .collect()
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let turns = self.turns();
+
+        for (n, segment) in self.segments.iter().enumerate() {
+            let Coord(i, j) = segment.start;
+            match n.checked_sub(1).map(|t| turns[t].1) {
+                None => write!(f, "Start at ({}, {}) heading {:?}", i, j, segment.dir)?,
+                Some(turn) => write!(f, "Turn {:?} at ({}, {}) to {:?}", turn, i, j, segment.dir)?,
+            }
+            writeln!(
+                f,
+                ": {} blocks, heat lost {}",
+                segment.length, segment.heat_lost
+            )?;
+        }
+
+        write!(
+            f,
+            "Total heat lost: {} over {} blocks",
+            self.heat_lost,
+            // An empty path, from a start that's already the goal, has no
+            // blocks at all rather than just the start
+            self.coords().len().saturating_sub(1)
+        )
+    }
+}
@@ -0,0 +1,356 @@
+//! Crucible movement rules beyond the puzzle's, with any number of starts and
+//! goals.
+//!
+//! Unlike the puzzle solvers, a state here has to remember which way the
+//! crucible is heading rather than just its axis, so that U-turns can be told
+//! apart from going straight on. There's also a state for a crucible at rest,
+//! which is where it starts, and where it ends up if it's allowed to stop at a
+//! goal partway through a run.
+
+use std::collections::VecDeque;
+
+use crate::dial::BucketQueue;
+use crate::{Board, Coord, Dir};
+
+const UNREACHED: usize = usize::MAX;
+
+const DIRS: [Dir; 4] = [Dir::North, Dir::East, Dir::South, Dir::West];
+
+/// Index of the state for a crucible at rest, after the four headings
+const REST: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrucibleRules {
+    /// Blocks the crucible must move in a straight line before it can turn
+    pub min_straight: usize,
+    /// Blocks the crucible can move in a straight line before it has to turn
+    pub max_straight: usize,
+    /// Extra heat lost every time the crucible turns. A U-turn counts as two
+    /// turns.
+    pub turn_penalty: usize,
+    /// Whether the crucible can reverse at the end of a run
+    pub u_turns: bool,
+    /// Whether the crucible has to have moved `min_straight` blocks in a line
+    /// before it can stop at a goal
+    pub stop_after_min_straight: bool,
+    pub starts: Vec<Coord>,
+    pub goals: Vec<Coord>,
+}
+
+impl CrucibleRules {
+    /// The rules for a crucible with the given limits on how far it moves in a
+    /// straight line, going from the top left to the bottom right of the board
+    pub fn new(board: &Board, min_straight: usize, max_straight: usize) -> Self {
+        let (rows, cols) = board.size();
+
+        Self {
+            min_straight,
+            max_straight,
+            turn_penalty: 0,
+            u_turns: false,
+            stop_after_min_straight: true,
+            starts: vec![Coord(0, 0)],
+            goals: vec![Coord(rows as i32 - 1, cols as i32 - 1)],
+        }
+    }
+
+    fn check(&self, board: &Board) -> Result<(), String> {
+        if self.max_straight == 0 || self.min_straight > self.max_straight {
+            return Err(format!(
+                "Can't move between {} and {} blocks in a straight line",
+                self.min_straight, self.max_straight
+            ));
+        }
+
+        if self.starts.is_empty() {
+            return Err("No starting blocks given".to_string());
+        }
+
+        for c in self.starts.iter().chain(&self.goals) {
+            if board.get(c).is_none() {
+                return Err(format!("({}, {}) is off the board", c.0, c.1));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The headings the crucible can set off in from a state, each with the
+    /// heat lost turning to face it
+    fn next_dirs(&self, kind: usize) -> Vec<(usize, usize)> {
+        if kind == REST {
+            return (0..4).map(|d| (d, 0)).collect();
+        }
+
+        // Headings are in clockwise order, so these are a turn either way
+        let mut dirs = vec![
+            ((kind + 1) % 4, self.turn_penalty),
+            ((kind + 3) % 4, self.turn_penalty),
+        ];
+        if self.u_turns {
+            dirs.push(((kind + 2) % 4, 2 * self.turn_penalty));
+        }
+
+        dirs
+    }
+}
+
+/// Manhattan distance from every block to the nearest of the goals. With no
+/// walls on the board this is a breadth-first search out from all of them at
+/// once, so it doesn't matter how many goals there are.
+fn nearest_goal_distances(rows: usize, cols: usize, goals: &[usize]) -> Vec<usize> {
+    let mut distances = vec![UNREACHED; rows * cols];
+    let mut frontier = VecDeque::new();
+
+    for &goal in goals {
+        if distances[goal] == UNREACHED {
+            distances[goal] = 0;
+            frontier.push_back(goal);
+        }
+    }
+
+    while let Some(n) = frontier.pop_front() {
+        let (y, x) = (n / cols, n % cols);
+        let neighbours = [
+            (y > 0).then(|| n - cols),
+            (y + 1 < rows).then(|| n + cols),
+            (x > 0).then(|| n - 1),
+            (x + 1 < cols).then(|| n + 1),
+        ];
+
+        for next in neighbours.into_iter().flatten() {
+            if distances[next] == UNREACHED {
+                distances[next] = distances[n] + 1;
+                frontier.push_back(next);
+            }
+        }
+    }
+
+    distances
+}
+
+/// The least heat lost getting to each goal, in the order the goals were given,
+/// or None for goals that can't be reached
+pub fn cheapest_to_goals(
+    board: &Board,
+    rules: &CrucibleRules,
+) -> Result<Vec<Option<usize>>, String> {
+    rules.check(board)?;
+
+    if rules.goals.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let (rows, cols) = board.size();
+    let cell = |c: &Coord| c.0 as usize * cols + c.1 as usize;
+    let goals: Vec<usize> = rules.goals.iter().map(cell).collect();
+
+    let heat: Vec<usize> = board.inner.concat();
+    let min_heat = heat.iter().copied().min().unwrap();
+
+    // Manhattan distance to the nearest goal, at the least heat per block, is
+    // admissible and consistent just as it is for a single goal
+    let estimate: Vec<usize> = nearest_goal_distances(rows, cols, &goals)
+        .into_iter()
+        .map(|distance| distance * min_heat)
+        .collect();
+    let mut is_goal = vec![false; rows * cols];
+    let mut is_start = vec![false; rows * cols];
+    for &goal in &goals {
+        is_goal[goal] = true;
+    }
+    for start in &rules.starts {
+        is_start[cell(start)] = true;
+    }
+
+    // A move raises the priority by at most its heat loss, its turns, and
+    // however much further it gets. The starts are all queued at once though,
+    // so the ring also has to reach from the lowest of them to the highest.
+    let max_heat = heat.iter().copied().max().unwrap();
+    let start_estimates: Vec<usize> = rules.starts.iter().map(|s| estimate[cell(s)]).collect();
+    let lowest = start_estimates.iter().copied().min().unwrap();
+    let highest = start_estimates.iter().copied().max().unwrap();
+    let spread = rules.max_straight * (max_heat + min_heat) + 2 * rules.turn_penalty;
+    let mut queue = BucketQueue::new(lowest, spread.max(highest - lowest));
+
+    // Indexed by block * 5 + heading, with heading REST for being at rest
+    let mut costs = vec![UNREACHED; rows * cols * 5];
+    let mut done = vec![false; rows * cols * 5];
+    let mut reached = vec![UNREACHED; rows * cols];
+    let mut goals_left = is_goal.iter().filter(|&&g| g).count();
+
+    for start in &rules.starts {
+        let state = cell(start) * 5 + REST;
+        costs[state] = 0;
+        queue.push(estimate[cell(start)], state);
+    }
+
+    while let Some((_, state)) = queue.pop() {
+        if done[state] {
+            continue;
+        }
+        done[state] = true;
+
+        let (n, kind) = (state / 5, state % 5);
+        let cost = costs[state];
+
+        if is_goal[n] && reached[n] == UNREACHED {
+            reached[n] = cost;
+            goals_left -= 1;
+            if goals_left == 0 {
+                break;
+            }
+        }
+
+        // Only the starts carry on from rest. Anywhere else, resting means the
+        // crucible stopped at a goal.
+        if kind == REST && !is_start[n] {
+            continue;
+        }
+
+        for (d, penalty) in rules.next_dirs(kind) {
+            let mut coord = Coord((n / cols) as i32, (n % cols) as i32);
+            let mut new_cost = cost + penalty;
+
+            for steps in 1..=rules.max_straight {
+                coord = coord.step(DIRS[d], 1);
+                if board.get(&coord).is_none() {
+                    break;
+                }
+
+                let new_cell = cell(&coord);
+                new_cost += heat[new_cell];
+
+                let next = if steps >= rules.min_straight {
+                    new_cell * 5 + d
+                } else if is_goal[new_cell] && !rules.stop_after_min_straight {
+                    new_cell * 5 + REST
+                } else {
+                    continue;
+                };
+
+                if new_cost < costs[next] {
+                    costs[next] = new_cost;
+                    queue.push(new_cost + estimate[new_cell], next);
+                }
+            }
+        }
+    }
+
+    Ok(goals
+        .iter()
+        .map(|&goal| (reached[goal] != UNREACHED).then_some(reached[goal]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Board {
+        Board::from_input(include_str!("../example.txt"))
+    }
+
+    #[test]
+    fn test_puzzle_rules() {
+        let board = example();
+
+        let rules = CrucibleRules::new(&board, 1, 3);
+        assert_eq!(cheapest_to_goals(&board, &rules), Ok(vec![Some(102)]));
+
+        let rules = CrucibleRules::new(&board, 4, 10);
+        assert_eq!(cheapest_to_goals(&board, &rules), Ok(vec![Some(94)]));
+    }
+
+    #[test]
+    fn test_multiple_goals() {
+        let board = example();
+        let rules = CrucibleRules {
+            goals: vec![Coord(12, 12), Coord(0, 0), Coord(0, 2), Coord(0, 3)],
+            ..CrucibleRules::new(&board, 4, 10)
+        };
+
+        // The start is free. The top row starts 2, 4, 1, 3, so the next two
+        // blocks along are too close to stop at on the way past, and have to be
+        // come back to the long way round.
+        let costs = cheapest_to_goals(&board, &rules).unwrap();
+        assert_eq!(costs[..2], [Some(94), Some(0)]);
+        assert!(costs[2].unwrap() > 4 + 1);
+        assert!(costs[3].unwrap() > 4 + 1 + 3);
+
+        // Unless it can stop partway along a run
+        let rules = CrucibleRules {
+            stop_after_min_straight: false,
+            ..rules
+        };
+        let costs = cheapest_to_goals(&board, &rules).unwrap();
+        assert_eq!(costs[2..], [Some(4 + 1), Some(4 + 1 + 3)]);
+    }
+
+    #[test]
+    fn test_far_apart_starts() {
+        // Every block loses 1. The starts' estimates of 38 and 2 are further
+        // apart than a move's spread of 2 * 3, and each goal is closest to a
+        // different one.
+        let board = Board::from_input(&["11111111111111111111\n"; 20].concat());
+        let rules = CrucibleRules {
+            starts: vec![Coord(0, 0), Coord(18, 18)],
+            goals: vec![Coord(19, 19), Coord(0, 1), Coord(10, 10)],
+            ..CrucibleRules::new(&board, 1, 3)
+        };
+
+        assert_eq!(
+            cheapest_to_goals(&board, &rules),
+            Ok(vec![Some(2), Some(1), Some(16)])
+        );
+    }
+
+    #[test]
+    fn test_turn_penalty() {
+        // Any route to the far corner turns at least once
+        let board = Board::from_input("1111\n1111");
+        let rules = CrucibleRules {
+            turn_penalty: 10,
+            ..CrucibleRules::new(&board, 1, 4)
+        };
+
+        assert_eq!(cheapest_to_goals(&board, &rules), Ok(vec![Some(4 + 10)]));
+    }
+
+    #[test]
+    fn test_u_turns() {
+        // In a single row the crucible can never turn, so it can only get to
+        // the blocks 2 or 3 along
+        let board = Board::from_input("111111");
+        let rules = CrucibleRules {
+            goals: vec![Coord(0, 1)],
+            turn_penalty: 5,
+            ..CrucibleRules::new(&board, 2, 3)
+        };
+        assert_eq!(cheapest_to_goals(&board, &rules), Ok(vec![None]));
+
+        // Going 3 along and then 2 back gets to the block 1 along
+        let rules = CrucibleRules {
+            u_turns: true,
+            ..rules
+        };
+        assert_eq!(
+            cheapest_to_goals(&board, &rules),
+            Ok(vec![Some(3 + 2 + 2 * 5)])
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let board = example();
+
+        let rules = CrucibleRules::new(&board, 5, 4);
+        assert!(cheapest_to_goals(&board, &rules).is_err());
+
+        let rules = CrucibleRules {
+            goals: vec![Coord(13, 0)],
+            ..CrucibleRules::new(&board, 1, 3)
+        };
+        assert!(cheapest_to_goals(&board, &rules).is_err());
+    }
+}
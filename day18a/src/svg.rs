@@ -0,0 +1,121 @@
+//! Drawing the lagoon as an SVG, with each stretch of trench in its own colour.
+
+use std::fmt::Write;
+
+use crate::{vertices, Coord, Instruction, PlanError, Reading, SteppedDir};
+
+/// Width and height of the drawing, in pixels. The lagoon is scaled to fit.
+const SIZE: f64 = 800.0;
+const MARGIN: f64 = 20.0;
+
+/// Render the dig plan, read the given way, as an SVG document. The interior is shaded, and each
+/// edge is drawn in the colour given by its instruction, one metre wide or at
+/// least wide enough to see. Plans that don't close are drawn anyway, to help
+/// see what went wrong.
+pub fn render(instructions: &[Instruction], reading: Reading) -> Result<String, PlanError> {
+    let directions: Vec<SteppedDir> = instructions.iter().map(|i| i.step(reading)).collect();
+    let points = vertices(&directions)?;
+
+    let min_row = points.iter().map(|p| p.0).min().unwrap();
+    let max_row = points.iter().map(|p| p.0).max().unwrap();
+    let min_col = points.iter().map(|p| p.1).min().unwrap();
+    let max_col = points.iter().map(|p| p.1).max().unwrap();
+
+    // Part two's distances run into the millions, so everything is scaled
+    // down by whichever side is longest. The sides are worked out as floats
+    // since they can be too long for an i64.
+    let rows = max_row as f64 - min_row as f64;
+    let cols = max_col as f64 - min_col as f64;
+    let scale = (SIZE - 2.0 * MARGIN) / rows.max(cols).max(1.0);
+    let width = cols * scale + 2.0 * MARGIN;
+    let height = rows * scale + 2.0 * MARGIN;
+
+    let position = |Coord(row, col): Coord| {
+        (
+            (col as f64 - min_col as f64) * scale + MARGIN,
+            (row as f64 - min_row as f64) * scale + MARGIN,
+        )
+    };
+    let stroke_width = scale.max(2.0);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.2} {h:.2}">"#,
+        w = width,
+        h = height
+    )
+    .unwrap();
+    svg += "<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n";
+
+    let outline: Vec<String> = points
+        .iter()
+        .map(|&p| {
+            let (x, y) = position(p);
+            format!("{:.2},{:.2}", x, y)
+        })
+        .collect();
+    writeln!(
+        svg,
+        r##"<polygon points="{}" fill="#5a6b7d" fill-opacity="0.35" stroke="none"/>"##,
+        outline.join(" ")
+    )
+    .unwrap();
+
+    for (instruction, pair) in instructions.iter().zip(points.windows(2)) {
+        let (x1, y1) = position(pair[0]);
+        let (x2, y2) = position(pair[1]);
+
+        writeln!(
+            svg,
+            r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke="{}" stroke-width="{:.2}" stroke-linecap="square"/>"#,
+            x1, y1, x2, y2, instruction.colour, stroke_width
+        )
+        .unwrap();
+    }
+
+    svg += "</svg>\n";
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Dir};
+
+    #[test]
+    fn test_render_example() {
+        let svg = render(
+            &parse(include_str!("../example.txt")).unwrap(),
+            Reading::Plain,
+        )
+        .unwrap();
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<polygon ").count(), 1);
+        assert_eq!(svg.matches("<line ").count(), 14);
+        assert!(svg.contains(r##"stroke="#70c710""##));
+        assert!(svg.contains(r##"stroke="#7a21e3""##));
+    }
+
+    #[test]
+    fn test_render_fits_huge_plans() {
+        let instructions: Vec<Instruction> = [Dir::East, Dir::South, Dir::West, Dir::North]
+            .into_iter()
+            .map(|dir| Instruction {
+                plain: dir * 10_000_000,
+                encoded: dir * 10_000_000,
+                colour: "#ff0000".to_string(),
+            })
+            .collect();
+
+        let svg = render(&instructions, Reading::Plain).unwrap();
+        let numbers: Vec<f64> = svg
+            .split(['"', ',', ' '])
+            .filter_map(|s| s.parse().ok())
+            .collect();
+
+        assert!(numbers.iter().all(|&n| (0.0..=SIZE).contains(&n)));
+    }
+}
@@ -1,9 +1,14 @@
+mod svg;
+mod volume;
+
+use std::fmt;
 use std::ops::{Add, Mul};
+use std::path::PathBuf;
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
-struct Coord(i32, i32);
+struct Coord(i64, i64);
 
-impl From<Coord> for (i32, i32) {
+impl From<Coord> for (i64, i64) {
     fn from(value: Coord) -> Self {
         (value.0, value.1)
     }
@@ -31,7 +36,7 @@ impl Add<Dir> for Coord {
 }
 
 // A direction stepped in a given direction
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct SteppedDir {
     dir: Dir,
     step: usize,
@@ -62,85 +67,475 @@ impl Mul<usize> for Dir {
     }
 }
 
-impl Add<SteppedDir> for Coord {
-    type Output = Coord;
+impl Coord {
+    /// The coordinate reached by digging from here, or None if it's outside the
+    /// range of an i64
+    fn checked_add(self, rhs: SteppedDir) -> Option<Coord> {
+        let step = i64::try_from(rhs.step).ok()?;
+
+        Some(match rhs.dir {
+            Dir::North => Coord(self.0.checked_sub(step)?, self.1),
+            Dir::East => Coord(self.0, self.1.checked_add(step)?),
+            Dir::South => Coord(self.0.checked_add(step)?, self.1),
+            Dir::West => Coord(self.0, self.1.checked_sub(step)?),
+        })
+    }
+}
+
+/// Ways a dig plan can fail to describe a lagoon. Instructions are counted
+/// from 0.
+#[derive(Debug, PartialEq, Eq)]
+enum PlanError {
+    /// The trench doesn't lead back to the start, but ends up at this offset
+    /// from it
+    NotClosed { offset: Coord },
+    /// Two edges of the trench meet somewhere other than a shared corner
+    Crossing { first: usize, second: usize },
+    /// Two edges of the trench run along each other
+    Overlapping { first: usize, second: usize },
+    /// An instruction digs past the range of an i64
+    OutOfRange { index: usize },
+    /// An instruction's trench is given no width
+    ZeroWidth { index: usize },
+    /// The lagoon has more blocks, or cubic metres, than fit in a u128
+    TooLarge,
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanError::NotClosed { offset } => write!(
+                f,
+                "trench ends {} rows and {} columns away from the start",
+                offset.0, offset.1
+            ),
+            PlanError::Crossing { first, second } => {
+                write!(f, "instructions {} and {} cross", first, second)
+            }
+            PlanError::Overlapping { first, second } => {
+                write!(f, "instructions {} and {} overlap", first, second)
+            }
+            PlanError::OutOfRange { index } => {
+                write!(f, "instruction {} digs out of range", index)
+            }
+            PlanError::ZeroWidth { index } => {
+                write!(f, "instruction {} digs a trench with no width", index)
+            }
+            PlanError::TooLarge => write!(f, "lagoon is too large to measure"),
+        }
+    }
+}
+
+/// The two ways of reading a line of the dig plan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reading {
+    /// The direction and distance written out, as in part one
+    Plain,
+    /// The direction and distance encoded in the colour, as in part two
+    Encoded,
+}
 
-    fn add(self, rhs: SteppedDir) -> Self::Output {
-        let step = rhs.step as i32;
-        match rhs.dir {
-            Dir::North => Coord(self.0 - step, self.1),
-            Dir::East => Coord(self.0, self.1 + step),
-            Dir::South => Coord(self.0 + step, self.1),
-            Dir::West => Coord(self.0, self.1 - step),
+/// One line of the dig plan, read both ways
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Instruction {
+    plain: SteppedDir,
+    encoded: SteppedDir,
+    /// Colour of the trench dug, as `#rrggbb`
+    colour: String,
+}
+
+impl Instruction {
+    fn step(&self, reading: Reading) -> SteppedDir {
+        match reading {
+            Reading::Plain => self.plain,
+            Reading::Encoded => self.encoded,
         }
     }
 }
 
-fn solution(input: &str) -> u64 {
-    let directions: Vec<SteppedDir> = input
+/// Ways a line of the dig plan can fail to parse. Lines are counted from 1.
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError {
+    /// A line without exactly a direction, distance and colour
+    WrongFieldCount { line: usize, found: usize },
+    /// A direction other than U, R, D or L
+    UnknownDirection { line: usize, found: String },
+    /// A distance that isn't a whole number
+    InvalidDistance { line: usize, found: String },
+    /// A colour not written as `(#rrggbb)`
+    MalformedColour { line: usize, found: String },
+    /// A colour whose last digit, giving the direction in part two, isn't 0 to 3
+    UnknownDirectionDigit { line: usize, found: char },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::WrongFieldCount { line, found } => {
+                write!(f, "expected 3 fields on line {} but found {}", line, found)
+            }
+            ParseError::UnknownDirection { line, found } => {
+                write!(f, "unknown direction {:?} on line {}", found, line)
+            }
+            ParseError::InvalidDistance { line, found } => {
+                write!(f, "invalid distance {:?} on line {}", found, line)
+            }
+            ParseError::MalformedColour { line, found } => {
+                write!(
+                    f,
+                    "expected (#rrggbb) on line {} but found {:?}",
+                    line, found
+                )
+            }
+            ParseError::UnknownDirectionDigit { line, found } => {
+                write!(f, "unknown direction digit {:?} on line {}", found, line)
+            }
+        }
+    }
+}
+
+/// Parse one line of the dig plan, like `R 6 (#70c710)`
+fn parse_line(text: &str, line: usize) -> Result<Instruction, ParseError> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    let [dir, step, colour] = fields[..] else {
+        return Err(ParseError::WrongFieldCount {
+            line,
+            found: fields.len(),
+        });
+    };
+
+    let dir = match dir {
+        "U" => Dir::North,
+        "R" => Dir::East,
+        "D" => Dir::South,
+        "L" => Dir::West,
+        _ => {
+            return Err(ParseError::UnknownDirection {
+                line,
+                found: dir.to_string(),
+            })
+        }
+    };
+    let step: usize = step.parse().map_err(|_| ParseError::InvalidDistance {
+        line,
+        found: step.to_string(),
+    })?;
+
+    let hex = colour
+        .strip_prefix("(#")
+        .and_then(|c| c.strip_suffix(')'))
+        .filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| ParseError::MalformedColour {
+            line,
+            found: colour.to_string(),
+        })?;
+
+    // The first five hex digits are the distance, and the last the direction
+    let encoded_step = usize::from_str_radix(&hex[..5], 16).unwrap();
+    let encoded_dir = match hex.as_bytes()[5] {
+        b'0' => Dir::East,
+        b'1' => Dir::South,
+        b'2' => Dir::West,
+        b'3' => Dir::North,
+        d => {
+            return Err(ParseError::UnknownDirectionDigit {
+                line,
+                found: d as char,
+            })
+        }
+    };
+
+    Ok(Instruction {
+        plain: dir * step,
+        encoded: encoded_dir * encoded_step,
+        colour: format!("#{}", hex),
+    })
+}
+
+/// Parse each line of the dig plan
+fn parse(input: &str) -> Result<Vec<Instruction>, ParseError> {
+    input
         .lines()
-        .map(|l| {
-            // Only change from part 1 is in the parsing logic here
-            let mut parts = l.split(' ');
-            let data = parts.nth(2).unwrap();
-
-            let step = usize::from_str_radix(&data[2..=6], 16).unwrap();
-            let dir = match &data[7..=7] {
-                "0" => Dir::East,
-                "1" => Dir::South,
-                "2" => Dir::West,
-                "3" => Dir::North,
-                d => panic!("Unrecognized direction {d}"),
-            };
+        .enumerate()
+        .map(|(n, text)| parse_line(text, n + 1))
+        .collect()
+}
+
+/// Corners of the trench, starting at the origin and ending wherever the last
+/// instruction leaves off
+fn vertices(directions: &[SteppedDir]) -> Result<Vec<Coord>, PlanError> {
+    let mut points = vec![Coord(0, 0)];
+
+    // Convert the directions into a list of coordinates
+    for (index, stepped_dir) in directions.iter().enumerate() {
+        let next = points
+            .last()
+            .unwrap()
+            .checked_add(*stepped_dir)
+            .ok_or(PlanError::OutOfRange { index })?;
+        points.push(next);
+    }
+
+    Ok(points)
+}
+
+/// Inclusive range of rows or columns
+type Span = (i64, i64);
+
+/// Overlap of two spans, if they overlap at all
+fn overlap((lo0, hi0): Span, (lo1, hi1): Span) -> Option<Span> {
+    let (lo, hi) = (lo0.max(lo1), hi0.min(hi1));
+    (lo <= hi).then_some((lo, hi))
+}
+
+/// Check that the plan digs a single closed loop that never crosses or runs
+/// back over itself, returning its corners
+fn validate(directions: &[SteppedDir]) -> Result<Vec<Coord>, PlanError> {
+    let mut points = vertices(directions)?;
+
+    let end = *points.last().unwrap();
+    if end != Coord(0, 0) {
+        return Err(PlanError::NotClosed { offset: end });
+    }
 
-            dir * step
+    // Each edge as the instruction it came from and the rows and columns it
+    // covers. Instructions that don't move anywhere can't cross anything, and
+    // would make their neighbours look like they meet.
+    let edges: Vec<(usize, Span, Span)> = points
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0] != pair[1])
+        .map(|(index, pair)| {
+            let (Coord(r0, c0), Coord(r1, c1)) = (pair[0], pair[1]);
+            (index, (r0.min(r1), r0.max(r1)), (c0.min(c1), c0.max(c1)))
         })
         .collect();
 
-    let perimeter = directions
-        .iter()
-        .map(|stepped_dir| stepped_dir.step)
-        .sum::<usize>() as u64;
+    for i in 0..edges.len() {
+        for j in i + 1..edges.len() {
+            let (first, rows0, cols0) = edges[i];
+            let (second, rows1, cols1) = edges[j];
 
-    let mut points = directions
-        .iter()
-        // Convert the directions into a list of coordinates
-        .fold(vec![Coord(0, 0)], |mut points, stepped_dir| {
-            let next = *points.last().unwrap() + *stepped_dir;
-            points.push(next);
+            let (Some(rows), Some(cols)) = (overlap(rows0, rows1), overlap(cols0, cols1)) else {
+                continue;
+            };
 
-            points
-        });
+            // Neighbouring edges always share the corner between them, and so
+            // do the last and first
+            let neighbours = j == i + 1 || (i == 0 && j == edges.len() - 1);
+
+            if rows.0 != rows.1 || cols.0 != cols.1 {
+                return Err(PlanError::Overlapping { first, second });
+            } else if !neighbours {
+                return Err(PlanError::Crossing { first, second });
+            }
+        }
+    }
 
-    // Since the directions form an enclosed region, the last point should coincide
-    // with the first.
-    assert_eq!(points.first(), points.last());
     points.pop();
+    Ok(points)
+}
 
-    // Use the shoelace formula on pairs of coordinates to compute the enclosed area
-    let shoelace_area = points
+/// Area enclosed by the loop through the points, and whether it goes round
+/// clockwise, with rows increasing downwards.
+///
+/// This is the shoelace formula, with each horizontal edge adding or taking
+/// away the strip between it and the top row, depending on which way it goes.
+/// Measuring from the top row and totalling each way separately keeps every
+/// term positive and within a u128, even when the points span all of an i64.
+fn enclosed_area(points: &[Coord]) -> Result<(u128, bool), PlanError> {
+    let top = points.iter().map(|p| p.0).min().unwrap_or(0);
+    let (mut west, mut east) = (0u128, 0u128);
+
+    for (p0, p1) in points.iter().zip(points.iter().cycle().skip(1)) {
+        let strip = p0.0.abs_diff(top) as u128 * p0.1.abs_diff(p1.1) as u128;
+        let total = if p0.1 > p1.1 { &mut west } else { &mut east };
+        *total = total.checked_add(strip).ok_or(PlanError::TooLarge)?;
+    }
+
+    Ok((west.abs_diff(east), west > east))
+}
+
+/// Area of the lagoon dug out by following the directions, including the trench
+fn lagoon_area(directions: &[SteppedDir]) -> Result<u128, PlanError> {
+    let points = validate(directions)?;
+
+    let perimeter = directions
         .iter()
-        .zip(points.iter().cycle().skip(1))
-        .take(points.len())
-        .map(|(p0, p1)| (p0.0 + p1.0) as i64 * (p0.1 - p1.1) as i64)
-        .sum::<i64>()
-        .unsigned_abs()
-        / 2;
-
-    // Since our perimeter is "thick", we can imagine that one half of it (say the top and
-    // right sides) are enclosed in the computed area, and the other half (bottom and left
-    // sides) aren't. To get the total area including the perimeter, we need to add the
-    // missing half back in. The +1 is probably to account for a missing corner by similar
-    // logic (counted 2, added 1 back from our half a perimeter, need one more if the
-    // polygon were a rectangle), but I just found it was needed by inspection.
-    shoelace_area + (perimeter / 2) + 1
+        .map(|stepped_dir| stepped_dir.step as u128)
+        .sum::<u128>();
+
+    let (shoelace_area, _) = enclosed_area(&points)?;
+
+    // The corners are the middles of blocks, so the blocks dug are the lattice
+    // points inside or on the loop through them. Pick's theorem says the area
+    // is the points inside plus half the points on the loop, minus one, and
+    // there's a point on the loop for every metre of trench. So the blocks dug
+    // are the area plus half the perimeter, plus one. `volume::raster_volume`
+    // checks this by counting blocks.
+    shoelace_area
+        .checked_add(perimeter / 2 + 1)
+        .ok_or(PlanError::TooLarge)
+}
+
+/// Anything that stops a dig plan being followed
+#[derive(Debug, PartialEq, Eq)]
+enum DigError {
+    Parse(ParseError),
+    Plan(PlanError),
+}
+
+impl From<ParseError> for DigError {
+    fn from(err: ParseError) -> Self {
+        DigError::Parse(err)
+    }
+}
+
+impl From<PlanError> for DigError {
+    fn from(err: PlanError) -> Self {
+        DigError::Plan(err)
+    }
+}
+
+impl fmt::Display for DigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DigError::Parse(err) => err.fmt(f),
+            DigError::Plan(err) => err.fmt(f),
+        }
+    }
+}
+
+/// Area of the lagoon dug by reading the instructions one way
+fn area_for(instructions: &[Instruction], reading: Reading) -> Result<u128, PlanError> {
+    let directions: Vec<SteppedDir> = instructions.iter().map(|i| i.step(reading)).collect();
+    lagoon_area(&directions)
+}
+
+/// Areas of the lagoons dug by reading the plan each way, parsing it just once
+fn both_areas(input: &str) -> Result<(u128, u128), DigError> {
+    let instructions = parse(input)?;
+
+    Ok((
+        area_for(&instructions, Reading::Plain)?,
+        area_for(&instructions, Reading::Encoded)?,
+    ))
+}
+
+fn solution(input: &str) -> Result<u128, DigError> {
+    Ok(area_for(&parse(input)?, Reading::Encoded)?)
 }
 
 fn main() {
+    // Usage: day18b [--svg FILE] [--both] [--width N] [--depth N] [--raster]
+    //
+    // --svg draws the lagoon to FILE, with each edge of the trench in its colour.
+    // --both prints the areas from reading the plan both ways, as in each part.
+    // --width and --depth print the volume dug with every trench N metres wide,
+    // and everything N metres deep. --raster counts every block dug instead,
+    // which is only practical for part one.
+    let mut svg_path: Option<PathBuf> = None;
+    let mut both = false;
+    let mut width: Option<usize> = None;
+    let mut depth: Option<u64> = None;
+    let mut raster = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--svg" => {
+                svg_path = Some(
+                    args.next()
+                        .unwrap_or_else(|| panic!("{} requires a value", arg))
+                        .into(),
+                )
+            }
+            "--both" => both = true,
+            "--raster" => raster = true,
+            "--width" | "--depth" => {
+                let value = args
+                    .next()
+                    .unwrap_or_else(|| panic!("{} requires a value", arg));
+                let value: u64 = value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid value {} for {}", value, arg));
+
+                if arg == "--width" {
+                    width = Some(value as usize);
+                } else {
+                    depth = Some(value);
+                }
+            }
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
+
     let input = include_str!("../input.txt");
-    let res = solution(input);
 
-    println!("Result: {}", res);
+    if let Some(path) = svg_path {
+        let svg = parse(input)
+            .map_err(DigError::from)
+            .and_then(|instructions| Ok(svg::render(&instructions, Reading::Encoded)?));
+
+        match svg {
+            Ok(svg) => {
+                std::fs::write(&path, svg).expect("Failed to write SVG");
+                println!("Wrote {}", path.display());
+            }
+            Err(err) => {
+                eprintln!("Can't draw dig plan: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if width.is_some() || depth.is_some() || raster {
+        let volume = parse(input)
+            .map_err(DigError::from)
+            .and_then(|instructions| {
+                let directions: Vec<SteppedDir> = instructions
+                    .iter()
+                    .map(|i| i.step(Reading::Encoded))
+                    .collect();
+                let widths = vec![width.unwrap_or(1); directions.len()];
+
+                let volume = if raster {
+                    volume::raster_volume
+                } else {
+                    volume::lagoon_volume
+                };
+
+                Ok(volume(&directions, &widths, depth.unwrap_or(1))?)
+            });
+
+        match volume {
+            Ok(volume) => println!("Volume: {}", volume),
+            Err(err) => {
+                eprintln!("Invalid dig plan: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if both {
+        match both_areas(input) {
+            Ok((plain, encoded)) => println!("Part one: {}\nPart two: {}", plain, encoded),
+            Err(err) => {
+                eprintln!("Invalid dig plan: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    match solution(input) {
+        Ok(res) => println!("Result: {}", res),
+        Err(err) => {
+            eprintln!("Invalid dig plan: {}", err);
+            std::process::exit(1);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -150,15 +545,180 @@ mod tests {
     #[test]
     fn test_example() {
         let input = include_str!("../example.txt");
-        let res = solution(input);
+        let res = solution(input).unwrap();
 
         assert_eq!(res, 952408144115);
     }
 
+    fn plan(steps: &[(Dir, usize)]) -> Vec<SteppedDir> {
+        steps.iter().map(|&(dir, step)| dir * step).collect()
+    }
+
+    #[test]
+    fn test_not_closed() {
+        let res = lagoon_area(&plan(&[(Dir::East, 4), (Dir::South, 2), (Dir::West, 3)]));
+
+        assert_eq!(
+            res,
+            Err(PlanError::NotClosed {
+                offset: Coord(2, 1)
+            })
+        );
+    }
+
+    #[test]
+    fn test_crossing() {
+        // A figure of eight, with the fourth edge cutting back across the first
+        let res = lagoon_area(&plan(&[
+            (Dir::East, 2),
+            (Dir::South, 2),
+            (Dir::West, 1),
+            (Dir::North, 3),
+            (Dir::West, 1),
+            (Dir::South, 1),
+        ]));
+
+        assert_eq!(
+            res,
+            Err(PlanError::Crossing {
+                first: 0,
+                second: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_overlapping() {
+        // Doubles back along the first edge
+        let res = lagoon_area(&plan(&[
+            (Dir::East, 2),
+            (Dir::West, 1),
+            (Dir::South, 1),
+            (Dir::West, 1),
+            (Dir::North, 1),
+        ]));
+
+        assert_eq!(
+            res,
+            Err(PlanError::Overlapping {
+                first: 0,
+                second: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let res = lagoon_area(&plan(&[(Dir::East, i64::MAX as usize), (Dir::East, 1)]));
+        assert_eq!(res, Err(PlanError::OutOfRange { index: 1 }));
+
+        let res = lagoon_area(&plan(&[(Dir::North, usize::MAX)]));
+        assert_eq!(res, Err(PlanError::OutOfRange { index: 0 }));
+    }
+
+    #[test]
+    fn test_huge_area() {
+        // A square whose area doesn't fit in 64 bits
+        let side = 1 << 40;
+        let res = lagoon_area(&plan(&[
+            (Dir::East, side),
+            (Dir::South, side),
+            (Dir::West, side),
+            (Dir::North, side),
+        ]));
+
+        assert_eq!(res, Ok((side as u128 + 1).pow(2)));
+    }
+
+    #[test]
+    fn test_area_at_limits() {
+        // Out to the top and bottom of an i64 and back, where the shoelace
+        // terms are too big for an i128
+        let m = i64::MAX as usize;
+        let res = lagoon_area(&plan(&[
+            (Dir::North, m),
+            (Dir::East, m),
+            (Dir::South, m),
+            (Dir::South, m),
+            (Dir::West, m),
+            (Dir::North, m),
+        ]));
+
+        assert_eq!(res, Ok((2 * m as u128 + 1) * (m as u128 + 1)));
+    }
+
+    #[test]
+    fn test_parse_both_ways() {
+        let instructions = parse("R 6 (#70c710)\nU 2 (#7a21e3)").unwrap();
+
+        assert_eq!(
+            instructions[0],
+            Instruction {
+                plain: Dir::East * 6,
+                encoded: Dir::East * 461937,
+                colour: "#70c710".to_string(),
+            }
+        );
+        assert_eq!(instructions[1].plain, Dir::North * 2);
+        assert_eq!(instructions[1].encoded, Dir::North * 500254);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input: &str| parse(input).unwrap_err();
+
+        assert_eq!(
+            error("R 6 (#70c710)\nR 6"),
+            ParseError::WrongFieldCount { line: 2, found: 2 }
+        );
+        assert_eq!(
+            error("X 6 (#70c710)"),
+            ParseError::UnknownDirection {
+                line: 1,
+                found: "X".to_string()
+            }
+        );
+        assert_eq!(
+            error("R -6 (#70c710)"),
+            ParseError::InvalidDistance {
+                line: 1,
+                found: "-6".to_string()
+            }
+        );
+        assert_eq!(
+            error("R 6 (#70c7g0)"),
+            ParseError::MalformedColour {
+                line: 1,
+                found: "(#70c7g0)".to_string()
+            }
+        );
+        assert_eq!(
+            error("R 6 #70c710"),
+            ParseError::MalformedColour {
+                line: 1,
+                found: "#70c710".to_string()
+            }
+        );
+        assert_eq!(
+            error("R 6 (#70c714)"),
+            ParseError::UnknownDirectionDigit {
+                line: 1,
+                found: '4'
+            }
+        );
+    }
+
+    #[test]
+    fn test_both_areas() {
+        let input = include_str!("../example.txt");
+
+        assert_eq!(both_areas(input), Ok((62, 952408144115)));
+    }
+
     #[test]
     fn test_input() {
         let input = include_str!("../input.txt");
-        let res = solution(input);
+        let res = solution(input).unwrap();
 
         assert_eq!(res, 88007104020978);
     }
@@ -0,0 +1,704 @@
+//! Lagoons dug with trenches wider than a metre, down to some depth.
+//!
+//! Extra width is dug outward from the plan's line, away from the lagoon, so
+//! the interior stays the same. Wide trenches can run into each other, or
+//! stick out past the end of a short neighbour, so the blocks dug aren't
+//! bounded by any simple loop. Instead, each trench is a rectangle, and the
+//! plane is cut along every rectangle's sides and every corner of the plan.
+//! The area is then the sum of the pieces that are inside the plan or
+//! covered by some trench.
+//!
+//! Coordinates are doubled throughout, so that the sides of blocks, as well
+//! as their middles, are whole numbers.
+
+use crate::{enclosed_area, validate, Coord, Dir, PlanError, SteppedDir};
+
+/// A point in doubled coordinates
+type Point = (i128, i128);
+
+/// One trench, for an instruction that digs at least one block
+struct Trench {
+    start: Coord,
+    dir: Dir,
+    step: i64,
+    width: usize,
+}
+
+fn unit(dir: Dir) -> Point {
+    match dir {
+        Dir::North => (-1, 0),
+        Dir::East => (0, 1),
+        Dir::South => (1, 0),
+        Dir::West => (0, -1),
+    }
+}
+
+/// Direction away from the lagoon, on the left of a clockwise plan and the
+/// right of an anticlockwise one
+fn outward(dir: Dir, clockwise: bool) -> Point {
+    let (r, c) = unit(dir);
+    if clockwise {
+        (-c, r)
+    } else {
+        (c, -r)
+    }
+}
+
+/// Trenches for each instruction that goes anywhere, and whether the plan
+/// goes round clockwise
+fn trenches(directions: &[SteppedDir], widths: &[usize]) -> Result<(Vec<Trench>, bool), PlanError> {
+    assert_eq!(
+        directions.len(),
+        widths.len(),
+        "Need one width per instruction"
+    );
+
+    if let Some(index) = widths.iter().position(|&w| w == 0) {
+        return Err(PlanError::ZeroWidth { index });
+    }
+
+    let points = validate(directions)?;
+
+    let (_, clockwise) = enclosed_area(&points)?;
+
+    let trenches = directions
+        .iter()
+        .zip(widths)
+        .zip(points)
+        .filter(|((stepped_dir, _), _)| stepped_dir.step > 0)
+        .map(|((stepped_dir, &width), start)| Trench {
+            start,
+            dir: stepped_dir.dir,
+            // Validation already checked every step fits
+            step: stepped_dir.step as i64,
+            width,
+        })
+        .collect();
+
+    Ok((trenches, clockwise))
+}
+
+/// Half-open range of rows or columns, in doubled coordinates
+type Range = (i128, i128);
+
+/// Range covering the blocks from `a` to `b`, inclusive, in either order
+fn blocks(a: i128, b: i128) -> Range {
+    (2 * a.min(b) - 1, 2 * a.max(b) + 1)
+}
+
+/// Range covering `width` blocks from `start`, going one way or the other.
+/// Wide trenches along the edge of the plan can go past the range of an i64.
+fn widened(start: i64, sign: i128, width: usize) -> Range {
+    let start = start as i128;
+    blocks(start, start + sign * (width as i128 - 1))
+}
+
+/// Rectangles of rows and columns covering every block dug for the
+/// trenches: a strip along each one, and at each turn, the blocks outside the
+/// corner that are beside both strips
+fn trench_rectangles(trenches: &[Trench], clockwise: bool) -> Vec<(Range, Range)> {
+    let mut rectangles = vec![];
+
+    for (k, b) in trenches.iter().enumerate() {
+        let a = &trenches[(k + trenches.len() - 1) % trenches.len()];
+        let Coord(row, col) = b.start;
+        let (dr, dc) = unit(b.dir);
+        let (or, oc) = outward(b.dir, clockwise);
+
+        let end_row = row + dr as i64 * b.step;
+        let end_col = col + dc as i64 * b.step;
+        rectangles.push(if dr == 0 {
+            (
+                widened(row, or, b.width),
+                blocks(col.into(), end_col.into()),
+            )
+        } else {
+            (
+                blocks(row.into(), end_row.into()),
+                widened(col, oc, b.width),
+            )
+        });
+
+        if a.dir != b.dir {
+            let (ar, ac) = outward(a.dir, clockwise);
+            // One of these goes across rows and the other across columns
+            let (rows, cols) = if ar == 0 {
+                (widened(row, or, b.width), widened(col, ac, a.width))
+            } else {
+                (widened(row, ar, a.width), widened(col, oc, b.width))
+            };
+            rectangles.push((rows, cols));
+        }
+    }
+
+    rectangles
+}
+
+/// Area of everything inside the loop through the trenches or covered by any
+/// of the rectangles
+fn dug_area(trenches: &[Trench], rectangles: &[(Range, Range)]) -> Result<u128, PlanError> {
+    let corners: Vec<Point> = trenches
+        .iter()
+        .map(|t| (2 * t.start.0 as i128, 2 * t.start.1 as i128))
+        .collect();
+
+    let mut rows: Vec<i128> = corners.iter().map(|p| p.0).collect();
+    let mut cols: Vec<i128> = corners.iter().map(|p| p.1).collect();
+    for &((top, bottom), (left, right)) in rectangles {
+        rows.extend([top, bottom]);
+        cols.extend([left, right]);
+    }
+    rows.sort_unstable();
+    rows.dedup();
+    cols.sort_unstable();
+    cols.dedup();
+
+    let row_index = |r: i128| rows.binary_search(&r).unwrap();
+    let col_index = |c: i128| cols.binary_search(&c).unwrap();
+
+    // Each vertical edge of the loop changes the winding number of everything
+    // to its right, in the bands of rows it spans. Each rectangle adds one to
+    // how many times everything in it is covered, and takes it off again past
+    // its right hand side.
+    let mut winding = vec![vec![0i64; cols.len()]; rows.len()];
+    let mut covered = vec![vec![0i64; cols.len()]; rows.len()];
+
+    for (p0, p1) in corners.iter().zip(corners.iter().cycle().skip(1)) {
+        if p0.1 != p1.1 {
+            continue;
+        }
+
+        let (top, bottom, sign) = if p0.0 < p1.0 {
+            (p0.0, p1.0, 1)
+        } else {
+            (p1.0, p0.0, -1)
+        };
+        let col = col_index(p0.1);
+        for band in &mut winding[row_index(top)..row_index(bottom)] {
+            band[col] += sign;
+        }
+    }
+
+    for &((top, bottom), (left, right)) in rectangles {
+        let (left, right) = (col_index(left), col_index(right));
+        for band in &mut covered[row_index(top)..row_index(bottom)] {
+            band[left] += 1;
+            band[right] -= 1;
+        }
+    }
+
+    // A piece's area in doubled coordinates can be too big for a u128 even
+    // when the area in blocks isn't, so whole blocks and the quarters of
+    // blocks left over at its edges are added up separately
+    let (mut whole, mut quarters) = (0u128, 0u128);
+    for i in 0..rows.len() - 1 {
+        let (mut inside, mut cover) = (0, 0);
+        for j in 0..cols.len() - 1 {
+            inside += winding[i][j];
+            cover += covered[i][j];
+            if inside != 0 || cover > 0 {
+                let height = (rows[i + 1] - rows[i]) as u128;
+                let width = (cols[j + 1] - cols[j]) as u128;
+                whole = (height / 2)
+                    .checked_mul(width / 2)
+                    .and_then(|blocks| whole.checked_add(blocks))
+                    .ok_or(PlanError::TooLarge)?;
+                quarters += 2 * (height / 2 * (width % 2) + height % 2 * (width / 2))
+                    + height % 2 * (width % 2);
+            }
+        }
+    }
+
+    whole.checked_add(quarters / 4).ok_or(PlanError::TooLarge)
+}
+
+/// Volume of the lagoon dug by following the directions, with each
+/// instruction's trench `widths` blocks wide and everything dug `depth` deep
+pub fn lagoon_volume(
+    directions: &[SteppedDir],
+    widths: &[usize],
+    depth: u64,
+) -> Result<u128, PlanError> {
+    let (trenches, clockwise) = trenches(directions, widths)?;
+
+    // A plan that doesn't go anywhere just digs out where it starts
+    if trenches.is_empty() {
+        return Ok(depth as u128);
+    }
+
+    dug_area(&trenches, &trench_rectangles(&trenches, clockwise))?
+        .checked_mul(depth as u128)
+        .ok_or(PlanError::TooLarge)
+}
+
+/// Volume found by marking every block dug, and flood filling around the
+/// outside of the plan's line to find the lagoon inside it. Any pockets the
+/// trenches close off outside the line are left alone. The fill is done in
+/// doubled coordinates, so it can squeeze between parts of the line in
+/// neighbouring blocks. Takes time and memory in proportion to the area, so
+/// this is only for checking `lagoon_volume` on small plans.
+pub fn raster_volume(
+    directions: &[SteppedDir],
+    widths: &[usize],
+    depth: u64,
+) -> Result<u128, PlanError> {
+    let (trenches, clockwise) = trenches(directions, widths)?;
+
+    if trenches.is_empty() {
+        return Ok(depth as u128);
+    }
+
+    // The line is in doubled coordinates, and the blocks dug aren't
+    let mut line: Vec<(i64, i64)> = vec![];
+    let mut dug: Vec<(i64, i64)> = vec![];
+    let offset =
+        |Coord(r, c): Coord, (dr, dc): Point, n: i64| (r + dr as i64 * n, c + dc as i64 * n);
+
+    for (k, b) in trenches.iter().enumerate() {
+        let out = outward(b.dir, clockwise);
+
+        for s in 0..=2 * b.step {
+            line.push(offset(Coord(2 * b.start.0, 2 * b.start.1), unit(b.dir), s));
+        }
+        for s in 0..=b.step {
+            let (r, c) = offset(b.start, unit(b.dir), s);
+            for w in 0..b.width as i64 {
+                dug.push(offset(Coord(r, c), out, w));
+            }
+        }
+
+        // Fill in the block outside each corner
+        let a = &trenches[(k + trenches.len() - 1) % trenches.len()];
+        if a.dir != b.dir {
+            let out_a = outward(a.dir, clockwise);
+            for i in 0..a.width as i64 {
+                for j in 0..b.width as i64 {
+                    let (r, c) = offset(b.start, out_a, i);
+                    dug.push(offset(Coord(r, c), out, j));
+                }
+            }
+        }
+    }
+
+    // Leave a margin so the outside is all one piece
+    let min_row = dug.iter().map(|d| d.0).min().unwrap() - 1;
+    let max_row = dug.iter().map(|d| d.0).max().unwrap() + 1;
+    let min_col = dug.iter().map(|d| d.1).min().unwrap() - 1;
+    let max_col = dug.iter().map(|d| d.1).max().unwrap() + 1;
+    let (rows, cols) = (
+        (max_row - min_row + 1) as usize,
+        (max_col - min_col + 1) as usize,
+    );
+
+    // Block (i, j) is at (2i, 2j) in the doubled grid
+    let (rows2, cols2) = (2 * rows - 1, 2 * cols - 1);
+    let mut on_line = vec![vec![false; cols2]; rows2];
+    for (r, c) in line {
+        on_line[(r - 2 * min_row) as usize][(c - 2 * min_col) as usize] = true;
+    }
+    let mut in_trench = vec![vec![false; cols]; rows];
+    for (r, c) in dug {
+        in_trench[(r - min_row) as usize][(c - min_col) as usize] = true;
+    }
+
+    let mut outside = vec![vec![false; cols2]; rows2];
+    let mut to_visit = vec![(0usize, 0usize)];
+    outside[0][0] = true;
+    while let Some((i, j)) = to_visit.pop() {
+        let neighbours = [
+            (i.wrapping_sub(1), j),
+            (i + 1, j),
+            (i, j.wrapping_sub(1)),
+            (i, j + 1),
+        ];
+
+        for (ni, nj) in neighbours {
+            if ni < rows2 && nj < cols2 && !on_line[ni][nj] && !outside[ni][nj] {
+                outside[ni][nj] = true;
+                to_visit.push((ni, nj));
+            }
+        }
+    }
+
+    let mut area = 0;
+    for i in 0..rows {
+        for j in 0..cols {
+            if !outside[2 * i][2 * j] || in_trench[i][j] {
+                area += 1;
+            }
+        }
+    }
+    Ok(area * depth as u128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lagoon_area;
+    use proptest::prelude::*;
+    use std::collections::{BTreeSet, HashMap};
+
+    fn plan(steps: &[(Dir, usize)]) -> Vec<SteppedDir> {
+        steps.iter().map(|&(dir, step)| dir * step).collect()
+    }
+
+    /// A plan going round columns of blocks side by side, each given as its
+    /// width, its top row and its height
+    fn column_plan(columns: &[(i64, i64, i64)]) -> Vec<SteppedDir> {
+        let mut corners = vec![];
+        let mut col = 0;
+        for &(width, top, _) in columns {
+            corners.push(Coord(top, col));
+            col += width;
+            corners.push(Coord(top, col));
+        }
+        for &(width, top, height) in columns.iter().rev() {
+            corners.push(Coord(top + height, col));
+            col -= width;
+            corners.push(Coord(top + height, col));
+        }
+
+        corners_to_plan(&corners)
+    }
+
+    /// Turn the corners of a loop into instructions, joining up runs in the
+    /// same direction and dropping any that don't go anywhere
+    fn corners_to_plan(corners: &[Coord]) -> Vec<SteppedDir> {
+        let mut directions: Vec<SteppedDir> = vec![];
+        for (c0, c1) in corners.iter().zip(corners.iter().cycle().skip(1)) {
+            let (dir, step) = match (c1.0 - c0.0, c1.1 - c0.1) {
+                (0, 0) => continue,
+                (dr, 0) if dr < 0 => (Dir::North, -dr),
+                (dr, 0) => (Dir::South, dr),
+                (0, dc) if dc < 0 => (Dir::West, -dc),
+                (0, dc) => (Dir::East, dc),
+                _ => unreachable!(),
+            };
+
+            match directions.last_mut() {
+                Some(last) if last.dir == dir => last.step += step as usize,
+                _ => directions.push(dir * step as usize),
+            }
+        }
+
+        directions
+    }
+
+    /// The outline of a shape grown one square at a time on a small grid,
+    /// each new square being the one `picks` chooses from those beside the
+    /// shape so far, scaled up by `scale`. These can have notches, spirals and
+    /// holes, and turn back on themselves along either axis. None if the
+    /// outline touches itself at a corner.
+    fn grown_plan(picks: &[usize], scale: i64) -> Option<Vec<SteppedDir>> {
+        const SIZE: i64 = 6;
+
+        let mut shape = BTreeSet::from([(SIZE / 2, SIZE / 2)]);
+        for &pick in picks {
+            let beside: BTreeSet<(i64, i64)> = shape
+                .iter()
+                .flat_map(|&(r, c)| [(r - 1, c), (r + 1, c), (r, c - 1), (r, c + 1)])
+                .filter(|&(r, c)| (0..SIZE).contains(&r) && (0..SIZE).contains(&c))
+                .filter(|square| !shape.contains(square))
+                .collect();
+            let Some(&square) = beside.iter().nth(pick % beside.len().max(1)) else {
+                break;
+            };
+            shape.insert(square);
+        }
+
+        // Go clockwise round each square, with its top left corner at its own
+        // row and column, and keep the sides with nothing on the other side
+        let mut next: HashMap<(i64, i64), Vec<(i64, i64)>> = HashMap::new();
+        for &(r, c) in &shape {
+            for (neighbour, from, to) in [
+                ((r - 1, c), (r, c), (r, c + 1)),
+                ((r, c + 1), (r, c + 1), (r + 1, c + 1)),
+                ((r + 1, c), (r + 1, c + 1), (r + 1, c)),
+                ((r, c - 1), (r + 1, c), (r, c)),
+            ] {
+                if !shape.contains(&neighbour) {
+                    next.entry(from).or_default().push(to);
+                }
+            }
+        }
+
+        // The top left corner of the first square is on the outer edge, so
+        // following the sides from there goes round the outside, and never
+        // round a hole
+        let start = *shape.first().unwrap();
+        let mut corners = vec![];
+        let mut here = start;
+        loop {
+            corners.push(Coord(here.0 * scale, here.1 * scale));
+            let [to] = next[&here][..] else {
+                return None;
+            };
+            if to == start {
+                break;
+            }
+            here = to;
+        }
+
+        Some(corners_to_plan(&corners))
+    }
+
+    /// Plans for any simple loop that fits on the grid, not just columns
+    fn grown() -> impl Strategy<Value = Vec<SteppedDir>> {
+        (prop::collection::vec(0..64usize, 0..24), 1..4i64).prop_filter_map(
+            "outline must be a single loop that validates",
+            |(picks, scale)| {
+                grown_plan(&picks, scale).filter(|directions| validate(directions).is_ok())
+            },
+        )
+    }
+
+    /// Columns where each overlaps the next, so the outline never touches itself
+    fn columns() -> impl Strategy<Value = Vec<(i64, i64, i64)>> {
+        prop::collection::vec((1..4i64, 0..6i64, 1..6i64), 1..7).prop_filter(
+            "neighbouring columns must overlap",
+            |columns| {
+                columns.windows(2).all(|pair| {
+                    let (_, t0, h0) = pair[0];
+                    let (_, t1, h1) = pair[1];
+                    t0.max(t1) < (t0 + h0).min(t1 + h1)
+                })
+            },
+        )
+    }
+
+    #[test]
+    fn test_example_volume() {
+        let directions: Vec<SteppedDir> = crate::parse(include_str!("../example.txt"))
+            .unwrap()
+            .iter()
+            .map(|i| i.plain)
+            .collect();
+        let widths = vec![1; directions.len()];
+
+        assert_eq!(lagoon_volume(&directions, &widths, 1), Ok(62));
+        assert_eq!(raster_volume(&directions, &widths, 1), Ok(62));
+        assert_eq!(lagoon_volume(&directions, &widths, 3), Ok(3 * 62));
+    }
+
+    #[test]
+    fn test_wide_trenches() {
+        // A 3 by 3 square whose trench is widened by a block all round, either
+        // way round
+        for square in [
+            plan(&[
+                (Dir::East, 2),
+                (Dir::South, 2),
+                (Dir::West, 2),
+                (Dir::North, 2),
+            ]),
+            plan(&[
+                (Dir::South, 2),
+                (Dir::East, 2),
+                (Dir::North, 2),
+                (Dir::West, 2),
+            ]),
+        ] {
+            assert_eq!(lagoon_volume(&square, &[2; 4], 2), Ok(2 * 5 * 5));
+            assert_eq!(raster_volume(&square, &[2; 4], 2), Ok(2 * 5 * 5));
+        }
+
+        // Only widening the top edge adds a row above it, and its corners
+        let square = plan(&[
+            (Dir::East, 2),
+            (Dir::South, 2),
+            (Dir::West, 2),
+            (Dir::North, 2),
+        ]);
+        assert_eq!(lagoon_volume(&square, &[2, 1, 1, 1], 1), Ok(3 * 3 + 3));
+    }
+
+    #[test]
+    fn test_overlapping_trenches() {
+        // A gap one block wide between two tall columns. Widening its sides
+        // fills the gap from both sides at once.
+        let directions = column_plan(&[(1, 0, 5), (1, 3, 2), (1, 0, 5)]);
+        let widths = vec![3; directions.len()];
+
+        assert_eq!(
+            lagoon_volume(&directions, &widths, 1),
+            raster_volume(&directions, &widths, 1)
+        );
+    }
+
+    #[test]
+    fn test_input_volume() {
+        // Wide enough that trenches close off pockets outside the plan, which
+        // aren't dug
+        let directions: Vec<SteppedDir> = crate::parse(include_str!("../input.txt"))
+            .unwrap()
+            .iter()
+            .map(|i| i.plain)
+            .collect();
+        let widths = vec![2; directions.len()];
+
+        assert_eq!(lagoon_volume(&directions, &widths, 1), Ok(39861));
+        assert_eq!(raster_volume(&directions, &widths, 1), Ok(39861));
+    }
+
+    #[test]
+    fn test_grown_plan() {
+        // Growing upwards from the middle, then one to the left at the top
+        assert_eq!(
+            grown_plan(&[0, 0, 0, 0], 2),
+            Some(plan(&[
+                (Dir::East, 4),
+                (Dir::South, 8),
+                (Dir::West, 2),
+                (Dir::North, 6),
+                (Dir::West, 2),
+                (Dir::North, 2),
+            ]))
+        );
+
+        // A notch, where the outline heads west and then back east again
+        let directions = grown_plan(&[3, 3, 3, 3], 1).unwrap();
+        assert_eq!(
+            directions,
+            plan(&[
+                (Dir::East, 2),
+                (Dir::South, 1),
+                (Dir::West, 1),
+                (Dir::South, 1),
+                (Dir::West, 3),
+                (Dir::North, 1),
+                (Dir::East, 2),
+                (Dir::North, 1),
+            ])
+        );
+        let widths = vec![2; directions.len()];
+        assert_eq!(
+            lagoon_volume(&directions, &widths, 1),
+            raster_volume(&directions, &widths, 1)
+        );
+
+        // Two squares meeting only at a corner, with the square between them
+        // left as a hole
+        assert_eq!(grown_plan(&[0, 0, 1, 6, 2, 5], 1), None);
+    }
+
+    #[test]
+    fn test_pocket_between_neighbouring_runs() {
+        // Runs of the line in neighbouring blocks wall in a block outside the
+        // loop, which is still outside it
+        let directions = plan(&[
+            (Dir::East, 4),
+            (Dir::South, 6),
+            (Dir::West, 5),
+            (Dir::North, 1),
+            (Dir::West, 1),
+            (Dir::North, 2),
+            (Dir::East, 1),
+            (Dir::South, 1),
+            (Dir::East, 2),
+            (Dir::North, 1),
+            (Dir::East, 1),
+            (Dir::North, 2),
+            (Dir::West, 1),
+            (Dir::South, 1),
+            (Dir::West, 1),
+            (Dir::North, 2),
+        ]);
+        let widths = vec![1; directions.len()];
+
+        assert_eq!(lagoon_area(&directions), Ok(41));
+        assert_eq!(raster_volume(&directions, &widths, 1), Ok(41));
+        assert_eq!(lagoon_volume(&directions, &widths, 1), Ok(41));
+    }
+
+    #[test]
+    fn test_volume_at_limits() {
+        let m = i64::MAX as usize;
+        let directions = plan(&[
+            (Dir::North, m),
+            (Dir::East, m),
+            (Dir::South, m),
+            (Dir::South, m),
+            (Dir::West, m),
+            (Dir::North, m),
+        ]);
+        let blocks = (2 * m as u128 + 1) * (m as u128 + 1);
+        assert_eq!(lagoon_area(&directions), Ok(blocks));
+        assert_eq!(lagoon_volume(&directions, &[1; 6], 1), Ok(blocks));
+
+        // Wider trenches go a block past the top and bottom of an i64, and
+        // past the right hand side
+        let wider = (2 * m as u128 + 3) * (m as u128 + 3);
+        assert_eq!(lagoon_volume(&directions, &[2; 6], 1), Ok(wider));
+
+        // Twice that is more than a u128 holds
+        assert_eq!(
+            lagoon_volume(&directions, &[2; 6], 2),
+            Err(PlanError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn test_zero_width() {
+        let square = plan(&[
+            (Dir::East, 2),
+            (Dir::South, 2),
+            (Dir::West, 2),
+            (Dir::North, 2),
+        ]);
+
+        assert_eq!(
+            lagoon_volume(&square, &[1, 1, 0, 1], 1),
+            Err(PlanError::ZeroWidth { index: 2 })
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_formula_matches_raster(columns in columns()) {
+            let directions = column_plan(&columns);
+            let widths = vec![1; directions.len()];
+
+            let raster = raster_volume(&directions, &widths, 1).unwrap();
+            prop_assert_eq!(lagoon_area(&directions), Ok(raster));
+            prop_assert_eq!(lagoon_volume(&directions, &widths, 1), Ok(raster));
+        }
+
+        #[test]
+        fn test_volume_matches_raster(
+            columns in columns(),
+            seed in prop::collection::vec(1..4usize, 32),
+            depth in 1..5u64,
+        ) {
+            let directions = column_plan(&columns);
+            let widths: Vec<usize> = seed.iter().cycle().take(directions.len()).copied().collect();
+
+            prop_assert_eq!(
+                lagoon_volume(&directions, &widths, depth),
+                raster_volume(&directions, &widths, depth)
+            );
+        }
+
+        #[test]
+        fn test_formula_matches_raster_on_any_loop(directions in grown()) {
+            let widths = vec![1; directions.len()];
+
+            let raster = raster_volume(&directions, &widths, 1).unwrap();
+            prop_assert_eq!(lagoon_area(&directions), Ok(raster));
+            prop_assert_eq!(lagoon_volume(&directions, &widths, 1), Ok(raster));
+        }
+
+        #[test]
+        fn test_volume_matches_raster_on_any_loop(
+            directions in grown(),
+            seed in prop::collection::vec(1..4usize, 64),
+            depth in 1..5u64,
+        ) {
+            let widths: Vec<usize> = seed.iter().cycle().take(directions.len()).copied().collect();
+
+            prop_assert_eq!(
+                lagoon_volume(&directions, &widths, depth),
+                raster_volume(&directions, &widths, depth)
+            );
+        }
+    }
+}
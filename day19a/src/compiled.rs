@@ -0,0 +1,456 @@
+//! Workflows compiled down to a flat list of range tests, for sorting large
+//! numbers of parts quickly.
+//!
+//! Each rule becomes one instruction: if the part's rating in some category is
+//! within a range, jump to a target, otherwise fall through to the next
+//! instruction. A workflow is its rules laid out one after another, and a
+//! `Goto` jumps straight to the first instruction of the workflow it names, so
+//! sorting a part never looks anything up by name. Unconditional rules test
+//! for the full range, so they always jump. A `!=` condition tests for its
+//! value the other way round, jumping when the rating is outside the range.
+//!
+//! Categories are given fixed slots when the workflows are compiled, in the
+//! order the parts declare them, so a part is just its ratings in that order.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use crate::{Dest, Op, Rule};
+
+/// A part's ratings, indexed by category slot
+pub type Part = Vec<u32>;
+
+/// Jump targets past the end of any program, for the two final destinations
+const ACCEPT: u32 = u32::MAX;
+const REJECT: u32 = u32::MAX - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Instruction {
+    slot: u32,
+    lo: u32,
+    hi: u32,
+    /// Whether to jump when the rating is inside the range, or outside it
+    inside: bool,
+    target: u32,
+}
+
+#[derive(Debug)]
+pub struct Program {
+    code: Vec<Instruction>,
+    /// Workflow names, indexed by their interned IDs
+    names: Vec<String>,
+    /// Category names, indexed by slot
+    categories: Vec<String>,
+    /// Where sorting starts, at the `in` workflow
+    start: u32,
+}
+
+impl Program {
+    /// Compile the workflows for parts with the given categories, checking
+    /// that every workflow and category they refer to exists, that each ends
+    /// with an unconditional rule, and that none of those reachable from `in`
+    /// go round in a cycle. A cycle is rejected even if no part could really
+    /// go all the way round it.
+    pub fn compile(
+        workflows: &HashMap<String, Vec<Rule>>,
+        categories: &[String],
+    ) -> Result<Self, String> {
+        // Unconditional rules test the first slot, which has to be there
+        if categories.is_empty() {
+            return Err("Parts need at least one category".to_string());
+        }
+
+        // Intern the names in sorted order, so the same workflows always give
+        // the same program. Each ID indexes where its workflow's instructions
+        // start.
+        let mut names: Vec<String> = workflows.keys().cloned().collect();
+        names.sort_unstable();
+        let ids: HashMap<&str, u32> = names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (name.as_str(), id as u32))
+            .collect();
+
+        let mut entries = Vec::with_capacity(names.len());
+        let mut offset = 0;
+        for name in &names {
+            entries.push(offset);
+            offset += workflows[name].len() as u32;
+        }
+
+        let target = |name: &str, dest: &Dest| match dest {
+            Dest::Accept => Ok(ACCEPT),
+            Dest::Reject => Ok(REJECT),
+            Dest::Goto(f) => ids
+                .get(f.as_str())
+                .map(|&id| entries[id as usize])
+                .ok_or_else(|| format!("Workflow {} goes to undefined workflow {}", name, f)),
+        };
+
+        let mut code = Vec::with_capacity(offset as usize);
+        for name in &names {
+            let rules = &workflows[name];
+            if !matches!(rules.last(), Some(Rule::Unconditional(_))) {
+                return Err(format!("Workflow {} doesn't end with a fallback", name));
+            }
+
+            for rule in rules {
+                code.push(match rule {
+                    Rule::Conditional {
+                        category,
+                        op,
+                        val,
+                        dest,
+                    } => {
+                        let slot = categories
+                            .iter()
+                            .position(|c| c == category)
+                            .ok_or_else(|| format!("Unknown category {}", category))?;
+                        // A condition nothing satisfies, like `x<0`, gets an
+                        // empty range and never jumps
+                        let val = *val;
+                        let (lo, hi) = match op {
+                            Op::Gt => val.checked_add(1).map_or((1, 0), |lo| (lo, u32::MAX)),
+                            Op::Ge => (val, u32::MAX),
+                            Op::Lt => val.checked_sub(1).map_or((1, 0), |hi| (0, hi)),
+                            Op::Le => (0, val),
+                            Op::Eq | Op::Ne => (val, val),
+                        };
+
+                        Instruction {
+                            slot: slot as u32,
+                            lo,
+                            hi,
+                            inside: !matches!(op, Op::Ne),
+                            target: target(name, dest)?,
+                        }
+                    }
+                    Rule::Unconditional(dest) => Instruction {
+                        slot: 0,
+                        lo: 0,
+                        hi: u32::MAX,
+                        inside: true,
+                        target: target(name, dest)?,
+                    },
+                });
+            }
+        }
+
+        let start = ids
+            .get("in")
+            .map(|&id| entries[id as usize])
+            .ok_or("No in workflow")?;
+
+        if let Some(cycle) = find_cycle(workflows, "in") {
+            return Err(format!(
+                "Workflows go round in a cycle through {}",
+                cycle.join(" -> ")
+            ));
+        }
+
+        Ok(Self {
+            code,
+            names,
+            categories: categories.to_vec(),
+            start,
+        })
+    }
+
+    /// Number of workflows, and of instructions they compiled to
+    pub fn size(&self) -> (usize, usize) {
+        (self.names.len(), self.code.len())
+    }
+
+    /// Category names, in the order of their slots
+    pub fn categories(&self) -> &[String] {
+        &self.categories
+    }
+
+    /// Sort a part, returning true if it's accepted. `compile` makes sure the
+    /// workflows can't go round in a loop, so this always returns.
+    pub fn run(&self, part: &[u32]) -> bool {
+        let mut pc = self.start;
+
+        loop {
+            let instruction = &self.code[pc as usize];
+            let rating = part[instruction.slot as usize];
+
+            if (instruction.lo <= rating && rating <= instruction.hi) == instruction.inside {
+                match instruction.target {
+                    ACCEPT => return true,
+                    REJECT => return false,
+                    target => pc = target,
+                }
+            } else {
+                pc += 1;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+/// The workflows of a cycle reachable from `start`, in order and ending back
+/// at the first, found by a depth-first search. Every goto has to be to a
+/// defined workflow.
+fn find_cycle(workflows: &HashMap<String, Vec<Rule>>, start: &str) -> Option<Vec<String>> {
+    let mut visits: HashMap<&str, Visit> = HashMap::from([(start, Visit::InProgress)]);
+
+    // Each entry is a workflow on the current path, and how many of its rules
+    // have been looked at
+    let mut stack: Vec<(&str, usize)> = vec![(start, 0)];
+
+    while let Some(&mut (name, ref mut next)) = stack.last_mut() {
+        let Some(rule) = workflows[name].get(*next) else {
+            visits.insert(name, Visit::Done);
+            stack.pop();
+            continue;
+        };
+        *next += 1;
+
+        let (Rule::Conditional { dest, .. } | Rule::Unconditional(dest)) = rule;
+        let Dest::Goto(target) = dest else {
+            continue;
+        };
+        match visits.get(target.as_str()) {
+            None => {
+                visits.insert(target, Visit::InProgress);
+                stack.push((target, 0));
+            }
+            Some(Visit::InProgress) => {
+                let from = stack.iter().position(|&(n, _)| n == target).unwrap();
+                let mut cycle: Vec<String> =
+                    stack[from..].iter().map(|&(n, _)| n.to_string()).collect();
+                cycle.push(target.clone());
+                return Some(cycle);
+            }
+            Some(Visit::Done) => {}
+        }
+    }
+
+    None
+}
+
+/// Parse a part like `{x=787,m=2655,a=1222,s=2876}`. The categories can be in
+/// any order, but each of those given has to be there exactly once.
+pub fn parse_part(text: &str, categories: &[String]) -> Result<Part, String> {
+    let inner = text
+        .strip_prefix('{')
+        .and_then(|t| t.strip_suffix('}'))
+        .ok_or_else(|| format!("Part {} isn't wrapped in braces", text))?;
+
+    let mut part = vec![0; categories.len()];
+    let mut seen = vec![false; categories.len()];
+    for field in inner.split(',') {
+        let (category, rating) = field
+            .split_once('=')
+            .ok_or_else(|| format!("Expected category=rating but found {}", field))?;
+        let slot = categories
+            .iter()
+            .position(|c| c == category)
+            .ok_or_else(|| format!("Unknown category {}", category))?;
+
+        if seen[slot] {
+            return Err(format!("Category {} given twice", category));
+        }
+        seen[slot] = true;
+        part[slot] = rating
+            .parse()
+            .map_err(|_| format!("Invalid rating {} for {}", rating, category))?;
+    }
+
+    if let Some(slot) = seen.iter().position(|&s| !s) {
+        return Err(format!("No rating for {}", categories[slot]));
+    }
+
+    Ok(part)
+}
+
+/// Totals over all the parts sorted
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Tally {
+    pub parts: u64,
+    pub accepted: u64,
+    /// Sum of every rating of every accepted part
+    pub accepted_ratings: u64,
+}
+
+/// Sort each part read from `reader`, one per line, writing `A` or `R` on a
+/// line of its own for each. Blank lines are skipped.
+pub fn stream(
+    program: &Program,
+    mut reader: impl BufRead,
+    mut writer: impl Write,
+) -> Result<Tally, String> {
+    let mut tally = Tally::default();
+
+    // Reuse one buffer for every line, rather than allocating for each
+    let mut buffer = String::new();
+    for n in 1.. {
+        buffer.clear();
+        let read = reader
+            .read_line(&mut buffer)
+            .map_err(|err| format!("Failed to read line {}: {}", n, err))?;
+        if read == 0 {
+            break;
+        }
+
+        let line = buffer.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let part =
+            parse_part(line, &program.categories).map_err(|err| format!("Line {}: {}", n, err))?;
+        let accepted = program.run(&part);
+
+        tally.parts += 1;
+        if accepted {
+            tally.accepted += 1;
+            tally.accepted_ratings += part.iter().map(|&r| r as u64).sum::<u64>();
+        }
+
+        writer
+            .write_all(if accepted { b"A\n" } else { b"R\n" })
+            .map_err(|err| format!("Failed to write result: {}", err))?;
+    }
+
+    writer
+        .flush()
+        .map_err(|err| format!("Failed to write result: {}", err))?;
+    Ok(tally)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apply_workflows, declared_categories, parse_workflows};
+
+    fn example() -> (HashMap<String, Vec<Rule>>, &'static str) {
+        let (workflows, parts) = include_str!("../example.txt").split_once("\n\n").unwrap();
+        (parse_workflows(workflows), parts)
+    }
+
+    fn xmas() -> Vec<String> {
+        declared_categories("").unwrap()
+    }
+
+    #[test]
+    fn test_compile() {
+        let (workflows, _) = example();
+        let program = Program::compile(&workflows, &xmas()).unwrap();
+
+        assert_eq!(program.size(), (11, 25));
+
+        // Conditions on categories the parts don't have
+        let categories = ["x", "m", "a"].map(String::from);
+        assert!(Program::compile(&workflows, &categories).is_err());
+
+        let mut workflows = workflows;
+        workflows.remove("qqz");
+        assert!(Program::compile(&workflows, &xmas()).is_err());
+        workflows.remove("in");
+        assert!(Program::compile(&workflows, &xmas()).is_err());
+
+        assert!(Program::compile(&parse_workflows("in{A}"), &[]).is_err());
+    }
+
+    #[test]
+    fn test_compile_rejects_cycles() {
+        let workflows = parse_workflows("in{x>10:a,R}\na{m<5:b,A}\nb{a>1:a,R}");
+        assert_eq!(
+            Program::compile(&workflows, &xmas()).unwrap_err(),
+            "Workflows go round in a cycle through a -> b -> a"
+        );
+
+        // Even when the goto round the cycle can never be taken
+        let workflows = parse_workflows("in{x<1:in,A}");
+        assert!(Program::compile(&workflows, &xmas()).is_err());
+
+        // Cycles that can't be reached from in can't trap a part
+        let workflows = parse_workflows("in{x>10:b,A}\nb{R}\nc{d}\nd{c}");
+        assert!(Program::compile(&workflows, &xmas()).is_ok());
+    }
+
+    #[test]
+    fn test_matches_interpreter() {
+        for input in [include_str!("../example.txt"), include_str!("../input.txt")] {
+            let (workflows, parts) = input.split_once("\n\n").unwrap();
+            let workflows = parse_workflows(workflows);
+            let categories = declared_categories(parts).unwrap();
+            let program = Program::compile(&workflows, &categories).unwrap();
+
+            for line in parts.lines() {
+                let part = parse_part(line, &categories).unwrap();
+                let map: HashMap<String, u32> =
+                    categories.iter().cloned().zip(part.clone()).collect();
+
+                assert_eq!(program.run(&part), apply_workflows(&workflows, &map));
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        let (workflows, parts) = example();
+        let program = Program::compile(&workflows, &xmas()).unwrap();
+
+        let mut output = vec![];
+        let tally = stream(&program, parts.as_bytes(), &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "A\nR\nA\nR\nA\n");
+        assert_eq!(
+            tally,
+            Tally {
+                parts: 5,
+                accepted: 3,
+                accepted_ratings: 19114,
+            }
+        );
+
+        let err = stream(&program, "{x=1,m=2,a=3,s=4}\n{x=1}".as_bytes(), vec![]).unwrap_err();
+        assert_eq!(err, "Line 2: No rating for m");
+    }
+
+    #[test]
+    fn test_parse_part() {
+        let xmas = xmas();
+        assert_eq!(parse_part("{s=4,a=3,m=2,x=1}", &xmas), Ok(vec![1, 2, 3, 4]));
+        assert_eq!(
+            parse_part("{x=4000000000,m=2,a=3,s=4}", &xmas),
+            Ok(vec![4_000_000_000, 2, 3, 4])
+        );
+        assert!(parse_part("x=1,m=2,a=3,s=4", &xmas).is_err());
+        assert!(parse_part("{x=1,m=2,a=3,s=4,x=5}", &xmas).is_err());
+        assert!(parse_part("{x=1,m=2,a=3,q=4}", &xmas).is_err());
+        assert!(parse_part("{x=1,m=2,a=3,s=-4}", &xmas).is_err());
+        assert!(parse_part("{x=1,m=2,a=3,s=5000000000}", &xmas).is_err());
+    }
+
+    #[test]
+    fn test_richer_conditions() {
+        let workflows = parse_workflows(
+            "in{weight>=10:heavy,colour==3:A,colour!=7:R,weight<=2:R,A}\nheavy{weight>4000000000:R,A}",
+        );
+        let categories = ["weight", "colour"].map(String::from);
+        let program = Program::compile(&workflows, &categories).unwrap();
+
+        for (part, accepted) in [
+            (vec![10, 0], true),
+            (vec![4_000_000_001, 0], false),
+            (vec![9, 3], true),
+            (vec![9, 4], false),
+            (vec![9, 7], true),
+            (vec![2, 7], false),
+            (vec![3, 7], true),
+        ] {
+            let map: HashMap<String, u32> = categories.iter().cloned().zip(part.clone()).collect();
+            assert_eq!(program.run(&part), accepted, "{:?}", part);
+            assert_eq!(apply_workflows(&workflows, &map), accepted, "{:?}", part);
+        }
+    }
+}
@@ -1,18 +1,33 @@
+mod compiled;
+
 use regex::Regex;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::io::BufWriter;
+use std::time::Instant;
+
+/// Categories parts have when there are none listed to declare them
+const DEFAULT_CATEGORIES: [&str; 4] = ["x", "m", "a", "s"];
 
 #[derive(Debug)]
 enum Op {
     Gt,
+    Ge,
     Lt,
+    Le,
+    Eq,
+    Ne,
 }
 
 impl Op {
-    fn cmp(&self, lhs: &u16, rhs: &u16) -> bool {
+    fn cmp(&self, lhs: &u32, rhs: &u32) -> bool {
         match self {
             Op::Gt => lhs > rhs,
+            Op::Ge => lhs >= rhs,
             Op::Lt => lhs < rhs,
+            Op::Le => lhs <= rhs,
+            Op::Eq => lhs == rhs,
+            Op::Ne => lhs != rhs,
         }
     }
 }
@@ -29,7 +44,7 @@ enum Rule {
     Conditional {
         category: String,
         op: Op,
-        val: u16,
+        val: u32,
         dest: Dest,
     },
     Unconditional(Dest),
@@ -37,7 +52,7 @@ enum Rule {
 
 /// Apply workflows until the part is accepted or reject. Returns true if the
 /// part is accepted, otherwise false.
-fn apply_workflows(workflows: &HashMap<String, Vec<Rule>>, part: &HashMap<String, u16>) -> bool {
+fn apply_workflows(workflows: &HashMap<String, Vec<Rule>>, part: &HashMap<String, u32>) -> bool {
     let mut flow = &workflows["in"];
 
     loop {
@@ -73,14 +88,14 @@ fn apply_workflows(workflows: &HashMap<String, Vec<Rule>>, part: &HashMap<String
     }
 }
 
-fn solution(input: &str) -> u64 {
-    let (workflows, parts) = input.split_once("\n\n").unwrap();
-
+/// Parse the workflows, one per line, like `px{a<2006:qkq,m>2090:A,rfg}`
+fn parse_workflows(workflows: &str) -> HashMap<String, Vec<Rule>> {
     let workflow_re = Regex::new(r"(?<name>[a-z]+)\{(?<rules>.+)}").unwrap();
     let comp_rule_re =
-        Regex::new(r"(?<category>[xmas])(?<op>[<>])(?<val>\d+):(?<dest>[a-zA-Z]+)").unwrap();
+        Regex::new(r"^(?<category>[a-z]+)(?<op><=|>=|==|!=|<|>)(?<val>\d+):(?<dest>[a-zA-Z]+)$")
+            .unwrap();
 
-    let workflows: HashMap<String, Vec<Rule>> = workflows
+    workflows
         .lines()
         .map(|l| {
             let caps = workflow_re.captures(l).expect("Found no matches");
@@ -91,8 +106,17 @@ fn solution(input: &str) -> u64 {
                 .map(|rule| {
                     if let Some(caps) = comp_rule_re.captures(rule) {
                         let category = caps["category"].to_string();
-                        let op = if &caps["op"] == ">" { Op::Gt } else { Op::Lt };
-                        let val: u16 = caps["val"].parse().unwrap();
+                        let op = match &caps["op"] {
+                            ">" => Op::Gt,
+                            ">=" => Op::Ge,
+                            "<" => Op::Lt,
+                            "<=" => Op::Le,
+                            "==" => Op::Eq,
+                            _ => Op::Ne,
+                        };
+                        let val: u32 = caps["val"]
+                            .parse()
+                            .unwrap_or_else(|_| panic!("Value too large in {}", rule));
                         let dest = match &caps["dest"] {
                             "A" => Dest::Accept,
                             "R" => Dest::Reject,
@@ -117,21 +141,70 @@ fn solution(input: &str) -> u64 {
 
             (name, rules)
         })
-        .collect();
+        .collect()
+}
+
+/// Categories declared by the first part listed, in the order given there,
+/// or x, m, a and s if there are no parts
+fn declared_categories(parts: &str) -> Result<Vec<String>, String> {
+    let Some(first) = parts.lines().find(|l| !l.trim().is_empty()) else {
+        return Ok(DEFAULT_CATEGORIES.map(String::from).to_vec());
+    };
+
+    let inner = first
+        .trim()
+        .strip_prefix('{')
+        .and_then(|t| t.strip_suffix('}'))
+        .ok_or_else(|| format!("Part {} isn't wrapped in braces", first))?;
+
+    let mut categories: Vec<String> = vec![];
+    for field in inner.split(',') {
+        let (category, _) = field
+            .split_once('=')
+            .ok_or_else(|| format!("Expected category=rating but found {}", field))?;
+        // Rules can only name categories made of lowercase letters
+        if category.is_empty() || !category.bytes().all(|b| b.is_ascii_lowercase()) {
+            return Err(format!(
+                "Category {:?} isn't all lowercase letters, so no rule can use it",
+                category
+            ));
+        }
+        if categories.iter().any(|c| c == category) {
+            return Err(format!("Category {} given twice", category));
+        }
+        categories.push(category.to_string());
+    }
+
+    Ok(categories)
+}
 
-    let part_re = Regex::new(r"\{x=(?<x>\d+),m=(?<m>\d+),a=(?<a>\d+),s=(?<s>\d+)}").unwrap();
+fn solution(input: &str) -> u64 {
+    let (workflows, parts) = input.split_once("\n\n").unwrap();
+    let workflows = parse_workflows(workflows);
+
+    // Every part has to have the categories the first declares
+    let categories = declared_categories(parts).unwrap_or_else(|err| panic!("{}", err));
+    for rules in workflows.values() {
+        for rule in rules {
+            if let Rule::Conditional { category, .. } = rule {
+                if !categories.contains(category) {
+                    panic!("No part has a rating for {}", category);
+                }
+            }
+        }
+    }
 
+    // Convert parts into a hash map of category -> value so that we can more
+    // easily do a dynamic lookup than if it were a struct
     let parts = parts.lines().map(|part| {
-        let caps = part_re
-            .captures(part)
-            .unwrap_or_else(|| panic!("Invalid part {}", part));
-
-        // Convert parts into a hash map of category -> value so that we can
-        // more easily do a dynamic lookup than if it were a struct
-        ["x", "m", "a", "s"]
-            .into_iter()
-            .map(|c| (c.to_string(), caps[c].parse().unwrap()))
-            .collect::<HashMap<String, u16>>()
+        let ratings = compiled::parse_part(part, &categories)
+            .unwrap_or_else(|err| panic!("Invalid part {}: {}", part, err));
+
+        categories
+            .iter()
+            .cloned()
+            .zip(ratings)
+            .collect::<HashMap<String, u32>>()
     });
 
     parts
@@ -141,7 +214,81 @@ fn solution(input: &str) -> u64 {
 }
 
 fn main() {
+    // Usage: day19a
+    //    or: day19a stream [--workflows FILE]
+    //
+    // stream compiles the workflows, from FILE or else the puzzle input, then
+    // sorts parts read from stdin one per line, writing A or R for each to
+    // stdout. Totals and timings go to stderr. Parts must have the categories
+    // declared by the first part listed after the workflows, or else x, m, a
+    // and s.
     let input = include_str!("../input.txt");
+
+    if std::env::args().nth(1).as_deref() == Some("stream") {
+        let mut workflows_path: Option<String> = None;
+
+        let mut args = std::env::args().skip(2);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--workflows" => {
+                    workflows_path = Some(
+                        args.next()
+                            .unwrap_or_else(|| panic!("{} requires a value", arg)),
+                    )
+                }
+                _ => panic!("Unknown argument {}", arg),
+            }
+        }
+
+        let text = match workflows_path {
+            Some(path) => std::fs::read_to_string(&path)
+                .unwrap_or_else(|err| panic!("Failed to read {}: {}", path, err)),
+            None => input.to_string(),
+        };
+        // Any parts after the workflows aren't sorted, but they do declare
+        // the categories
+        let (workflows, parts) = text.split_once("\n\n").unwrap_or((&text, ""));
+        let workflows = parse_workflows(workflows);
+
+        let program = declared_categories(parts)
+            .and_then(|categories| compiled::Program::compile(&workflows, &categories));
+        let program = match program {
+            Ok(program) => program,
+            Err(err) => {
+                eprintln!("Invalid workflows: {}", err);
+                std::process::exit(1);
+            }
+        };
+        let (flows, instructions) = program.size();
+        eprintln!(
+            "Compiled {} workflows to {} instructions, for parts rated on {}",
+            flows,
+            instructions,
+            program.categories().join(", ")
+        );
+
+        let started = Instant::now();
+        let stdout = std::io::stdout().lock();
+        match compiled::stream(&program, std::io::stdin().lock(), BufWriter::new(stdout)) {
+            Ok(tally) => {
+                let elapsed = started.elapsed();
+                eprintln!(
+                    "Accepted {} of {} parts in {:.3}s ({:.0} parts/s), ratings summing to {}",
+                    tally.accepted,
+                    tally.parts,
+                    elapsed.as_secs_f64(),
+                    tally.parts as f64 / elapsed.as_secs_f64(),
+                    tally.accepted_ratings
+                );
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let res = solution(input);
 
     println!("Result: {}", res);
@@ -159,6 +306,23 @@ mod tests {
         assert_eq!(res, 19114);
     }
 
+    #[test]
+    fn test_declared_categories() {
+        assert_eq!(
+            declared_categories("{weight=1,colour=2}"),
+            Ok(vec!["weight".to_string(), "colour".to_string()])
+        );
+        assert_eq!(
+            declared_categories("{Weight=1}"),
+            Err(
+                r#"Category "Weight" isn't all lowercase letters, so no rule can use it"#
+                    .to_string()
+            )
+        );
+        assert!(declared_categories("{a,b=1}").is_err());
+        assert!(declared_categories("{=1}").is_err());
+    }
+
     #[test]
     fn test_input() {
         let input = include_str!("../input.txt");
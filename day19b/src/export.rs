@@ -0,0 +1,161 @@
+//! Accepted regions along with the rules that led to them, written out as CSV
+//! or JSON for use outside the puzzle.
+
+use std::fmt::Write;
+
+use crate::region::Hyperrectangle;
+
+/// A rule that fired on the way to a region, by its index in its workflow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub workflow: String,
+    pub rule: usize,
+}
+
+/// A region of parts that's accepted, and the rules that send it there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcceptedRegion {
+    pub path: Vec<Step>,
+    pub region: Hyperrectangle,
+}
+
+impl AcceptedRegion {
+    /// The path as text, like `in:0 > px:2`
+    pub fn path_string(&self) -> String {
+        self.path
+            .iter()
+            .map(|step| format!("{}:{}", step.workflow, step.rule))
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+}
+
+/// One line per region, with its path, its volume, and the lowest and highest
+/// rating it covers in each category. Workflow and category names are only
+/// ever lowercase letters, which `declared_categories` makes sure of, so
+/// nothing needs quoting.
+pub fn to_csv(regions: &[AcceptedRegion], categories: &[String]) -> String {
+    let mut csv = String::from("path,volume");
+    for category in categories {
+        write!(csv, ",{}_min,{}_max", category, category).unwrap();
+    }
+    csv.push('\n');
+
+    for accepted in regions {
+        write!(
+            csv,
+            "{},{}",
+            accepted.path_string(),
+            accepted.region.volume(categories)
+        )
+        .unwrap();
+        for category in categories {
+            let bound = accepted.region.bound(category);
+            write!(csv, ",{},{}", bound.lo, bound.hi).unwrap();
+        }
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// An array with an object per region, like
+/// `{"path": [{"workflow": "in", "rule": 0}], "volume": 4, "bounds": {"x": [1, 4]}}`
+pub fn to_json(regions: &[AcceptedRegion], categories: &[String]) -> String {
+    let mut json = String::from("[");
+
+    for (i, accepted) in regions.iter().enumerate() {
+        json.push_str(if i == 0 { "\n  " } else { ",\n  " });
+
+        let path: Vec<String> = accepted
+            .path
+            .iter()
+            .map(|step| {
+                format!(
+                    r#"{{"workflow": "{}", "rule": {}}}"#,
+                    step.workflow, step.rule
+                )
+            })
+            .collect();
+        let bounds: Vec<String> = categories
+            .iter()
+            .map(|category| {
+                let bound = accepted.region.bound(category);
+                format!(r#""{}": [{}, {}]"#, category, bound.lo, bound.hi)
+            })
+            .collect();
+
+        write!(
+            json,
+            r#"{{"path": [{}], "volume": {}, "bounds": {{{}}}}}"#,
+            path.join(", "),
+            accepted.region.volume(categories),
+            bounds.join(", ")
+        )
+        .unwrap();
+    }
+
+    json.push_str(if regions.is_empty() { "]\n" } else { "\n]\n" });
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region::Interval;
+    use crate::{accepted_regions, declared_categories, parse_workflows};
+
+    fn regions(workflows: &str) -> Vec<AcceptedRegion> {
+        let categories = declared_categories("").unwrap();
+        accepted_regions(&parse_workflows(workflows), &categories, Interval::full()).unwrap()
+    }
+
+    #[test]
+    fn test_paths() {
+        let (workflows, _) = include_str!("../example.txt").split_once("\n\n").unwrap();
+        let categories = declared_categories("").unwrap();
+        let regions = regions(workflows);
+
+        assert_eq!(
+            regions
+                .iter()
+                .map(|r| r.region.volume(&categories))
+                .sum::<u128>(),
+            167409079868000
+        );
+        // Rules that are fallen through aren't steps, only the ones that fire
+        assert_eq!(regions[0].path_string(), "in:0 > px:0 > qkq:0");
+        assert!(regions
+            .iter()
+            .all(|r| r.path.first().map(|s| s.workflow.as_str()) == Some("in")));
+    }
+
+    #[test]
+    fn test_csv() {
+        let regions = regions("in{x<11:a,R}\na{m>3990:R,A}");
+        let categories = ["x", "m"].map(String::from);
+
+        assert_eq!(
+            to_csv(&regions, &categories),
+            "path,volume,x_min,x_max,m_min,m_max\nin:0 > a:1,39900,1,10,1,3990\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let regions = regions("in{x<11:A,m>3990:R,A}");
+        let categories = ["x", "m"].map(String::from);
+
+        assert_eq!(
+            to_json(&regions, &categories),
+            concat!(
+                "[\n",
+                r#"  {"path": [{"workflow": "in", "rule": 0}], "volume": 40000, "bounds": {"x": [1, 10], "m": [1, 4000]}},"#,
+                "\n",
+                r#"  {"path": [{"workflow": "in", "rule": 2}], "volume": 15920100, "bounds": {"x": [11, 4000], "m": [1, 3990]}}"#,
+                "\n]\n"
+            )
+        );
+        assert_eq!(to_json(&[], &categories), "[]\n");
+    }
+}
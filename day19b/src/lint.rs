@@ -0,0 +1,400 @@
+//! Checks for mistakes and dead weight in workflow definitions.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::region::{Hyperrectangle, Interval};
+use crate::{Dest, Rule};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// There's no `in` workflow to start from
+    NoEntry,
+    /// The rule goes to a workflow that isn't defined
+    UndefinedTarget { target: String },
+    /// The workflow's last rule has a condition, so parts it doesn't match
+    /// have nowhere to go
+    NoFallback,
+    /// No path from `in` leads to the workflow
+    Unreachable,
+    /// Following the rule leads back round to its own workflow, through these
+    /// workflows in order
+    Cycle { through: Vec<String> },
+    /// Every part that gets as far as the rule has already been sent somewhere
+    /// by an earlier rule
+    NeverFires,
+    /// Every rule of the workflow goes to the same place, so the conditions
+    /// make no difference
+    SameDestination { dest: String },
+}
+
+/// A problem with one rule of a workflow. For problems with a workflow as a
+/// whole, the rule is its first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub workflow: String,
+    pub rule: usize,
+    pub problem: Problem,
+}
+
+impl Finding {
+    /// Whether the problem stops the workflows being followed at all, rather
+    /// than just making some of them pointless
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self.problem,
+            Problem::NoEntry
+                | Problem::UndefinedTarget { .. }
+                | Problem::NoFallback
+                | Problem::Cycle { .. }
+        )
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} rule {}: ", self.workflow, self.rule)?;
+
+        match &self.problem {
+            Problem::NoEntry => write!(f, "no workflow named in to start from"),
+            Problem::UndefinedTarget { target } => {
+                write!(f, "goes to undefined workflow {}", target)
+            }
+            Problem::NoFallback => {
+                write!(f, "last rule has a condition, with no fallback after it")
+            }
+            Problem::Unreachable => write!(f, "workflow can't be reached from in"),
+            Problem::Cycle { through } => {
+                write!(f, "goes round in a cycle through {}", through.join(" -> "))
+            }
+            Problem::NeverFires => write!(f, "no part can reach this rule and match it"),
+            Problem::SameDestination { dest } => {
+                write!(f, "every rule of the workflow goes to {}", dest)
+            }
+        }
+    }
+}
+
+fn dest(rule: &Rule) -> &Dest {
+    match rule {
+        Rule::Conditional { dest, .. } => dest,
+        Rule::Unconditional(dest) => dest,
+    }
+}
+
+/// Workflows each rule of a workflow goes to, with the rule's index
+fn gotos(rules: &[Rule]) -> impl Iterator<Item = (usize, &str)> {
+    rules
+        .iter()
+        .enumerate()
+        .filter_map(|(i, rule)| match dest(rule) {
+            Dest::Goto(target) => Some((i, target.as_str())),
+            _ => None,
+        })
+}
+
+/// Check all the workflows, giving findings sorted by workflow and rule. Rules
+/// only count as never firing if no part rated within `range` could match
+/// them.
+pub fn lint(workflows: &HashMap<String, Vec<Rule>>, range: Interval) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut names: Vec<&String> = workflows.keys().collect();
+    names.sort();
+
+    let mut finding = |workflow: &str, rule: usize, problem: Problem| {
+        findings.push(Finding {
+            workflow: workflow.to_string(),
+            rule,
+            problem,
+        })
+    };
+
+    if !workflows.contains_key("in") {
+        finding("in", 0, Problem::NoEntry);
+    }
+
+    for &name in &names {
+        let rules = &workflows[name];
+
+        for (i, target) in gotos(rules) {
+            if !workflows.contains_key(target) {
+                finding(
+                    name,
+                    i,
+                    Problem::UndefinedTarget {
+                        target: target.to_string(),
+                    },
+                );
+            }
+        }
+
+        if let Some(Rule::Conditional { .. }) = rules.last() {
+            finding(name, rules.len() - 1, Problem::NoFallback);
+        }
+
+        for i in never_fires(rules, range) {
+            finding(name, i, Problem::NeverFires);
+        }
+
+        if rules.len() > 1 && rules.iter().all(|rule| dest(rule) == dest(&rules[0])) {
+            let dest = match dest(&rules[0]) {
+                Dest::Accept => "A".to_string(),
+                Dest::Reject => "R".to_string(),
+                Dest::Goto(target) => target.clone(),
+            };
+            finding(name, 0, Problem::SameDestination { dest });
+        }
+    }
+
+    let reachable = reachable(workflows);
+    for &name in &names {
+        if !reachable.contains(name.as_str()) {
+            finding(name, 0, Problem::Unreachable);
+        }
+    }
+
+    for (workflow, rule, through) in cycles(workflows, &names) {
+        finding(workflow, rule, Problem::Cycle { through });
+    }
+
+    findings.sort_by(|a, b| (&a.workflow, a.rule).cmp(&(&b.workflow, b.rule)));
+    findings
+}
+
+/// Indices of rules that no part rated within `range` and reaching the
+/// workflow could ever match, because the earlier rules have already taken
+/// every part that would
+fn never_fires(rules: &[Rule], range: Interval) -> Vec<usize> {
+    // Parts that get past each rule, which can take more than one region to
+    // describe in general
+    let mut remaining = vec![Hyperrectangle::new(range)];
+    let mut dead = vec![];
+
+    for (i, rule) in rules.iter().enumerate() {
+        match rule {
+            Rule::Conditional { condition, .. } => {
+                let fires = remaining.iter().any(|region| {
+                    condition
+                        .holds()
+                        .into_iter()
+                        .any(|holds| !region.restrict(&condition.key, holds).is_empty())
+                });
+                if !fires {
+                    dead.push(i);
+                }
+
+                remaining = remaining
+                    .iter()
+                    .flat_map(|region| {
+                        condition
+                            .fails()
+                            .into_iter()
+                            .map(|fails| region.restrict(&condition.key, fails))
+                    })
+                    .filter(|region| !region.is_empty())
+                    .collect();
+            }
+            Rule::Unconditional(_) => {
+                if remaining.is_empty() {
+                    dead.push(i);
+                }
+                remaining.clear();
+            }
+        }
+    }
+
+    dead
+}
+
+/// Names of all the workflows reachable from `in`
+fn reachable(workflows: &HashMap<String, Vec<Rule>>) -> HashSet<&str> {
+    let mut seen = HashSet::new();
+    let mut frontier = VecDeque::new();
+
+    if workflows.contains_key("in") {
+        seen.insert("in");
+        frontier.push_back("in");
+    }
+
+    while let Some(name) = frontier.pop_front() {
+        for (_, target) in gotos(&workflows[name]) {
+            if workflows.contains_key(target) && seen.insert(target) {
+                frontier.push_back(target);
+            }
+        }
+    }
+
+    seen
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    Unvisited,
+    InProgress,
+    Done,
+}
+
+/// Every rule whose goto closes a cycle found by a depth-first search, along
+/// with the workflows in that cycle, starting from the one it goes back to
+fn cycles<'a>(
+    workflows: &'a HashMap<String, Vec<Rule>>,
+    names: &[&'a String],
+) -> Vec<(&'a str, usize, Vec<String>)> {
+    let mut visits: HashMap<&str, Visit> = names
+        .iter()
+        .map(|name| (name.as_str(), Visit::Unvisited))
+        .collect();
+    let mut found = vec![];
+
+    for &root in names {
+        if visits[root.as_str()] != Visit::Unvisited {
+            continue;
+        }
+
+        // Each entry is a workflow on the current path, and how many of its
+        // rules have been looked at
+        let mut stack: Vec<(&str, usize)> = vec![(root, 0)];
+        visits.insert(root, Visit::InProgress);
+
+        while let Some(&mut (name, ref mut next)) = stack.last_mut() {
+            let rules = &workflows[name];
+            let Some(rule) = rules.get(*next) else {
+                visits.insert(name, Visit::Done);
+                stack.pop();
+                continue;
+            };
+            let index = *next;
+            *next += 1;
+
+            let Dest::Goto(target) = dest(rule) else {
+                continue;
+            };
+            match visits.get(target.as_str()) {
+                Some(Visit::Unvisited) => {
+                    visits.insert(target, Visit::InProgress);
+                    stack.push((target, 0));
+                }
+                Some(Visit::InProgress) => {
+                    let start = stack.iter().position(|&(n, _)| n == target).unwrap();
+                    let mut through: Vec<String> =
+                        stack[start..].iter().map(|&(n, _)| n.to_string()).collect();
+                    through.push(target.clone());
+                    found.push((name, index, through));
+                }
+                // Already checked, or undefined and reported separately
+                Some(Visit::Done) | None => {}
+            }
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_workflows;
+
+    fn problems(workflows: &str) -> Vec<(String, usize, Problem)> {
+        lint(&parse_workflows(workflows), Interval::full())
+            .into_iter()
+            .map(|f| (f.workflow, f.rule, f.problem))
+            .collect()
+    }
+
+    #[test]
+    fn test_example() {
+        let (workflows, _) = include_str!("../example.txt").split_once("\n\n").unwrap();
+        let findings = lint(&parse_workflows(workflows), Interval::full());
+
+        assert_eq!(
+            findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "gd rule 0: every rule of the workflow goes to R",
+                "lnx rule 0: every rule of the workflow goes to A",
+            ]
+        );
+        assert!(!findings.iter().any(Finding::is_error));
+    }
+
+    #[test]
+    fn test_undefined_and_unreachable() {
+        assert_eq!(
+            problems("in{x>10:ab,A}\nabc{R}"),
+            [
+                ("abc".to_string(), 0, Problem::Unreachable),
+                (
+                    "in".to_string(),
+                    0,
+                    Problem::UndefinedTarget {
+                        target: "ab".to_string()
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            problems("px{x>10:A,R}"),
+            [
+                ("in".to_string(), 0, Problem::NoEntry),
+                ("px".to_string(), 0, Problem::Unreachable),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        let findings = lint(
+            &parse_workflows("in{x>10:ab,A}\nab{m<5:cd,R}\ncd{a>1:ab,A}"),
+            Interval::full(),
+        );
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].to_string(),
+            "cd rule 0: goes round in a cycle through ab -> cd -> ab"
+        );
+        assert!(findings[0].is_error());
+    }
+
+    #[test]
+    fn test_never_fires() {
+        // Nothing under 10 is left to be under 5, and nothing at all is left
+        // for the fallback
+        assert_eq!(
+            problems("in{x<10:R,x<5:A,x>9:A,R}"),
+            [
+                ("in".to_string(), 1, Problem::NeverFires),
+                ("in".to_string(), 3, Problem::NeverFires),
+            ]
+        );
+
+        // Conditions on other categories don't rule anything out
+        assert_eq!(problems("in{x<10:R,m<5:A,R}"), []);
+
+        // Ratings over 4000 only exist if the range being searched has them
+        assert_eq!(
+            problems("in{x>4000:A,R}"),
+            [("in".to_string(), 0, Problem::NeverFires)]
+        );
+        let workflows = parse_workflows("in{x>4000:A,R}");
+        assert_eq!(lint(&workflows, Interval::new(0, u32::MAX)), []);
+    }
+
+    #[test]
+    fn test_no_fallback() {
+        let findings = lint(&parse_workflows("in{x>5:A}"), Interval::full());
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].to_string(),
+            "in rule 0: last rule has a condition, with no fallback after it"
+        );
+        assert!(findings[0].is_error());
+
+        // Even when the conditions between them cover every part
+        assert_eq!(
+            problems("in{x<10:A,x>9:R}"),
+            [("in".to_string(), 1, Problem::NoFallback)]
+        );
+    }
+}
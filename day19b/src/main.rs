@@ -1,14 +1,28 @@
+mod export;
+mod lint;
+mod probability;
+mod region;
+
+use export::{AcceptedRegion, Step};
 use regex::Regex;
+use region::{Hyperrectangle, Interval};
 use std::collections::HashMap;
 use std::fmt::Debug;
 
+/// Categories parts have when there are none listed to declare them
+const DEFAULT_CATEGORIES: [&str; 4] = ["x", "m", "a", "s"];
+
 #[derive(Debug, Clone, Copy)]
 enum Op {
     Gt,
+    Ge,
     Lt,
+    Le,
+    Eq,
+    Ne,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 enum Dest {
     Accept,
     Reject,
@@ -19,21 +33,42 @@ enum Dest {
 struct Condition {
     key: String,
     op: Op,
-    val: u16,
+    val: u32,
 }
 
 impl Condition {
-    /// Get the logical complement of this condition
-    fn complement(self) -> Self {
-        let (op, val) = match self.op {
-            Op::Lt => (Op::Gt, self.val - 1),
-            Op::Gt => (Op::Lt, self.val + 1),
+    /// The ratings this condition is about, and whether it holds for those
+    /// ratings or for everything else
+    fn interval(&self) -> (Interval, bool) {
+        let val = self.val;
+        let interval = match self.op {
+            Op::Gt => val
+                .checked_add(1)
+                .map_or(Interval::empty(), |lo| Interval::new(lo, u32::MAX)),
+            Op::Ge => Interval::new(val, u32::MAX),
+            Op::Lt => val
+                .checked_sub(1)
+                .map_or(Interval::empty(), |hi| Interval::new(0, hi)),
+            Op::Le => Interval::new(0, val),
+            Op::Eq | Op::Ne => Interval::new(val, val),
         };
 
-        Self {
-            key: self.key,
-            op,
-            val,
+        (interval, !matches!(self.op, Op::Ne))
+    }
+
+    /// The ratings that satisfy this condition, in at most two ranges
+    fn holds(&self) -> Vec<Interval> {
+        match self.interval() {
+            (interval, true) => vec![interval],
+            (interval, false) => interval.complement().to_vec(),
+        }
+    }
+
+    /// The ratings that don't satisfy this condition, in at most two ranges
+    fn fails(&self) -> Vec<Interval> {
+        match self.interval() {
+            (interval, true) => interval.complement().to_vec(),
+            (interval, false) => vec![interval],
         }
     }
 }
@@ -44,77 +79,86 @@ enum Rule {
     Unconditional(Dest),
 }
 
-/// Find the conditions that result in a part being accepted.
+/// Send a region of parts on to a destination, by the rule last on `path`
+fn follow(
+    workflows: &HashMap<String, Vec<Rule>>,
+    dest: &Dest,
+    region: Hyperrectangle,
+    path: &mut Vec<Step>,
+    accepted: &mut Vec<AcceptedRegion>,
+) {
+    match dest {
+        Dest::Accept => accepted.push(AcceptedRegion {
+            path: path.clone(),
+            region,
+        }),
+        Dest::Reject => {}
+        Dest::Goto(f) => find_accept_conditions(workflows, f, 0, region, path, accepted),
+    }
+}
+
+/// Find the regions of parts that are accepted, out of those in `region` that
+/// reach rule `index` of workflow `start`.
 ///
-/// Conjunctions (ANDs) of conditions needed to accept a part are added to
-/// accepted_runs when one is found. accepted_runs as a whole is a disjunction (OR)
-/// of acceptable conditions.
+/// Each rule splits the parts reaching it in two: those its condition holds
+/// for, which go to its destination, and the rest, which go on to the next
+/// rule. Either side can take two regions, one each side of a range. The
+/// accepted regions found are pushed to `accepted`, along with the rules that
+/// fired on the way there, and since every split is into disjoint parts, no
+/// two of them overlap. Empty regions are dropped as soon as they appear.
+///
+/// `path` holds the rules that fired to get to `start`, and is left as it was
+/// found.
 fn find_accept_conditions(
     workflows: &HashMap<String, Vec<Rule>>,
     start: &str,
-    mut this_run: Vec<Condition>,
-    accepted_runs: &mut Vec<Vec<Condition>>,
+    index: usize,
+    region: Hyperrectangle,
+    path: &mut Vec<Step>,
+    accepted: &mut Vec<AcceptedRegion>,
 ) {
-    let flow = &workflows[start];
-
-    let mut conditions: Vec<Condition> = Vec::new();
-
-    for rule in flow {
-        match rule {
-            Rule::Unconditional(dest) => {
-                // Unconditional rules are always the end of a rule set,
-                // so these must all return
-                match dest {
-                    Dest::Accept => {
-                        // When we hit an accept, save the history that led here
-                        this_run.extend_from_slice(&conditions);
-                        accepted_runs.push(this_run);
-                    }
-                    Dest::Reject => {
-                        // When we hit a reject, discard the history
-                    }
-                    Dest::Goto(f) => {
-                        // When we hit an unconditional goto, add conditions from this
-                        // rule set and proceed from there.
-                        this_run.extend_from_slice(&conditions);
-                        find_accept_conditions(workflows, f, this_run, accepted_runs);
-                    }
+    if region.is_empty() {
+        return;
+    }
+
+    path.push(Step {
+        workflow: start.to_string(),
+        rule: index,
+    });
+
+    match &workflows[start][index] {
+        // Unconditional rules are always the end of a rule set
+        Rule::Unconditional(dest) => follow(workflows, dest, region, path, accepted),
+        Rule::Conditional { condition, dest } => {
+            for holds in condition.holds() {
+                let taken = region.restrict(&condition.key, holds);
+                if !taken.is_empty() {
+                    follow(workflows, dest, taken, path, accepted);
                 }
-                return;
             }
-            Rule::Conditional { condition, dest } => {
-                // Fork on the condition
-                let mut forked_run = this_run.clone();
-                let mut forked_conditions = conditions.clone();
-                forked_conditions.push(condition.clone());
-                forked_run.extend_from_slice(&forked_conditions);
-
-                match dest {
-                    Dest::Accept => {
-                        accepted_runs.push(forked_run);
-                    }
-                    Dest::Reject => {}
-                    Dest::Goto(f) => {
-                        find_accept_conditions(workflows, f, forked_run, accepted_runs);
-                    }
-                }
 
-                // Rules will never end in a conditional case. We push the complement
-                // of this condition since it must be false to reach the next rule.
-                conditions.push(condition.clone().complement())
+            // The condition must be false to reach the next rule, which isn't
+            // a step of its own
+            path.pop();
+            for fails in condition.fails() {
+                let rest = region.restrict(&condition.key, fails);
+                find_accept_conditions(workflows, start, index + 1, rest, path, accepted);
             }
-        };
+            return;
+        }
     }
-}
 
-fn solution(input: &str) -> u64 {
-    let (workflows, _) = input.split_once("\n\n").unwrap();
+    path.pop();
+}
 
+/// Parse the workflows, one per line, like `px{a<2006:qkq,m>2090:A,rfg}`
+fn parse_workflows(workflows: &str) -> HashMap<String, Vec<Rule>> {
     let workflow_re = Regex::new(r"(?<name>[a-z]+)\{(?<rules>.+)}").unwrap();
     let comp_rule_re =
-        Regex::new(r"(?<category>[xmas])(?<op>[<>])(?<val>\d+):(?<dest>[a-zA-Z]+)").unwrap();
+        Regex::new(r"^(?<category>[a-z]+)(?<op><=|>=|==|!=|<|>)(?<val>\d+):(?<dest>[a-zA-Z]+)$")
+            .unwrap();
 
-    let workflows: HashMap<String, Vec<Rule>> = workflows
+    workflows
         .lines()
         .map(|l| {
             let caps = workflow_re.captures(l).expect("Found no matches");
@@ -125,8 +169,17 @@ fn solution(input: &str) -> u64 {
                 .map(|rule| {
                     if let Some(caps) = comp_rule_re.captures(rule) {
                         let category = caps["category"].to_string();
-                        let op = if &caps["op"] == ">" { Op::Gt } else { Op::Lt };
-                        let val: u16 = caps["val"].parse().unwrap();
+                        let op = match &caps["op"] {
+                            ">" => Op::Gt,
+                            ">=" => Op::Ge,
+                            "<" => Op::Lt,
+                            "<=" => Op::Le,
+                            "==" => Op::Eq,
+                            _ => Op::Ne,
+                        };
+                        let val: u32 = caps["val"]
+                            .parse()
+                            .unwrap_or_else(|_| panic!("Value too large in {}", rule));
                         let dest = match &caps["dest"] {
                             "A" => Dest::Accept,
                             "R" => Dest::Reject,
@@ -153,49 +206,215 @@ fn solution(input: &str) -> u64 {
 
             (name, rules)
         })
-        .collect();
+        .collect()
+}
 
-    // Brute forcing doesn't work, there are over 2^47 combinations to check.
-    // The workflows form a tree, starting with the `in` node. Each path to an
-    // Accept leaf node of this tree is a set of rules that, when reduced,
-    // can give lower and upper bounds on each of our four variables. Calculating
-    // the volumes of the 4D hyper-rectangles bound by these bounds gives the
-    // desired answer.
-    let mut accepted_runs = Vec::new();
-    find_accept_conditions(&workflows, "in", Vec::new(), &mut accepted_runs);
-
-    // Maps with lower and upper bounds for each category, for each combination
-    // of rules that leads to an accepted state.
-    let regions = accepted_runs.iter().map(|conditions| {
-        // Tuples of inclusive (lower bound, upper bound) for each category
-        let bounds: HashMap<&str, (u16, u16)> = ["x", "m", "a", "s"]
-            .map(|c| (c, (1, 4000)))
-            .into_iter()
-            .collect();
-        conditions.iter().fold(bounds, |mut map, condition| {
-            let bound = map.get_mut(condition.key.as_str()).unwrap();
-            match condition.op {
-                Op::Gt => bound.0 = condition.val + 1,
-                Op::Lt => bound.1 = condition.val - 1,
+/// Categories declared by the first part listed, in the order given there,
+/// or x, m, a and s if there are no parts
+fn declared_categories(parts: &str) -> Result<Vec<String>, String> {
+    let Some(first) = parts.lines().find(|l| !l.trim().is_empty()) else {
+        return Ok(DEFAULT_CATEGORIES.map(String::from).to_vec());
+    };
+
+    let inner = first
+        .trim()
+        .strip_prefix('{')
+        .and_then(|t| t.strip_suffix('}'))
+        .ok_or_else(|| format!("Part {} isn't wrapped in braces", first))?;
+
+    let mut categories: Vec<String> = vec![];
+    for field in inner.split(',') {
+        let (category, _) = field
+            .split_once('=')
+            .ok_or_else(|| format!("Expected category=rating but found {}", field))?;
+        // Rules can only name categories made of lowercase letters
+        if category.is_empty() || !category.bytes().all(|b| b.is_ascii_lowercase()) {
+            return Err(format!(
+                "Category {:?} isn't all lowercase letters, so no rule can use it",
+                category
+            ));
+        }
+        if categories.iter().any(|c| c == category) {
+            return Err(format!("Category {} given twice", category));
+        }
+        categories.push(category.to_string());
+    }
+
+    Ok(categories)
+}
+
+/// The disjoint regions of parts with the given categories, each rated within
+/// `range`, that the workflows accept, in the order the search finds them
+fn accepted_regions(
+    workflows: &HashMap<String, Vec<Rule>>,
+    categories: &[String],
+    range: Interval,
+) -> Result<Vec<AcceptedRegion>, String> {
+    for rules in workflows.values() {
+        for rule in rules {
+            if let Rule::Conditional { condition, .. } = rule {
+                if !categories.contains(&condition.key) {
+                    return Err(format!("No part has a rating for {}", condition.key));
+                }
             }
+        }
+    }
 
-            map
-        })
-    });
+    let mut accepted = Vec::new();
+    find_accept_conditions(
+        workflows,
+        "in",
+        0,
+        Hyperrectangle::new(range),
+        &mut vec![],
+        &mut accepted,
+    );
 
-    // For each region, compute the 4D volume and sum those up
-    regions
-        .map(|region| {
-            region
-                .values()
-                .map(|(lower, upper)| (upper - lower + 1) as u64)
-                .product::<u64>()
-        })
-        .sum()
+    Ok(accepted)
+}
+
+/// Number of distinct parts with the given categories, each rated within
+/// `range`, that the workflows accept
+fn count_accepted(
+    workflows: &HashMap<String, Vec<Rule>>,
+    categories: &[String],
+    range: Interval,
+) -> Result<u128, String> {
+    Ok(accepted_regions(workflows, categories, range)?
+        .iter()
+        .map(|accepted| accepted.region.volume(categories))
+        .sum())
+}
+
+fn solution(input: &str) -> u128 {
+    let (workflows, parts) = input.split_once("\n\n").unwrap();
+    let workflows = parse_workflows(workflows);
+    let categories = declared_categories(parts).unwrap_or_else(|err| panic!("{}", err));
+
+    // Brute forcing doesn't work, there are over 2^47 combinations to check.
+    // The workflows form a tree, starting with the `in` node. Each path to an
+    // Accept leaf node of this tree narrows the ranges of our variables down
+    // to a hyper-rectangle, and no two paths share any parts. Summing the
+    // volumes of these hyper-rectangles gives the desired answer.
+    count_accepted(&workflows, &categories, Interval::full())
+        .unwrap_or_else(|err| panic!("{}", err))
 }
 
 fn main() {
+    // Usage: day19b
+    //    or: day19b lint [FILE]
+    //    or: day19b export csv|json [FILE]
+    //    or: day19b probability [CATEGORY=DISTRIBUTION]...
+    //
+    // lint checks the workflows, from FILE or else the puzzle input, and lists
+    // anything wrong with them, exiting with status 1 if any are errors rather
+    // than warnings. Any parts after the workflows are ignored.
+    //
+    // export writes the disjoint regions of parts the puzzle input's workflows
+    // accept, with the rules that led to each, to FILE or else stdout.
+    //
+    // probability gives the chance of a part being accepted when each of its
+    // ratings is drawn independently. A distribution is a uniform range like
+    // x=1-4000, or a histogram of weighted ranges like x=1-100:3,101-4000:1.
+    // Categories not given are uniform from 1 to 4000.
     let input = include_str!("../input.txt");
+
+    if std::env::args().nth(1).as_deref() == Some("lint") {
+        let text = match std::env::args().nth(2) {
+            Some(path) => std::fs::read_to_string(&path)
+                .unwrap_or_else(|err| panic!("Failed to read {}: {}", path, err)),
+            None => input.to_string(),
+        };
+
+        // Rules only count as never firing if they can't for any rating at
+        // all, as probability searches them all
+        let workflows = parse_workflows(text.split("\n\n").next().unwrap());
+        let findings = lint::lint(&workflows, Interval::new(0, u32::MAX));
+        for finding in &findings {
+            let level = if finding.is_error() {
+                "error"
+            } else {
+                "warning"
+            };
+            println!("{}: {}", level, finding);
+        }
+        println!("{} findings", findings.len());
+        if findings.iter().any(lint::Finding::is_error) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Undefined workflows, missing fallbacks and cycles would stop the search
+    // finishing
+    let (workflows, parts) = input.split_once("\n\n").unwrap();
+    let workflows = parse_workflows(workflows);
+    let errors: Vec<_> = lint::lint(&workflows, Interval::new(0, u32::MAX))
+        .into_iter()
+        .filter(lint::Finding::is_error)
+        .collect();
+    if !errors.is_empty() {
+        for error in errors {
+            eprintln!("Invalid workflows: {}", error);
+        }
+        std::process::exit(1);
+    }
+    let categories = declared_categories(parts).unwrap_or_else(|err| panic!("{}", err));
+
+    match std::env::args().nth(1).as_deref() {
+        Some("export") => {
+            let regions = accepted_regions(&workflows, &categories, Interval::full())
+                .unwrap_or_else(|err| panic!("{}", err));
+            let text = match std::env::args().nth(2).as_deref() {
+                Some("csv") => export::to_csv(&regions, &categories),
+                Some("json") => export::to_json(&regions, &categories),
+                _ => panic!("export requires a format, csv or json"),
+            };
+
+            match std::env::args().nth(3) {
+                Some(path) => {
+                    std::fs::write(&path, text)
+                        .unwrap_or_else(|err| panic!("Failed to write {}: {}", path, err));
+                    println!("Wrote {} regions to {}", regions.len(), path);
+                }
+                None => print!("{}", text),
+            }
+            return;
+        }
+        Some("probability") => {
+            let mut distributions: HashMap<String, probability::Distribution> = categories
+                .iter()
+                .map(|c| {
+                    (
+                        c.clone(),
+                        probability::Distribution::Uniform(Interval::full()),
+                    )
+                })
+                .collect();
+            for arg in std::env::args().skip(2) {
+                let (category, text) = arg
+                    .split_once('=')
+                    .unwrap_or_else(|| panic!("Expected CATEGORY=DISTRIBUTION but found {}", arg));
+                if !categories.iter().any(|c| c == category) {
+                    panic!("No part has a rating for {}", category);
+                }
+                let distribution = probability::Distribution::parse(text)
+                    .unwrap_or_else(|err| panic!("Invalid distribution for {}: {}", category, err));
+                distributions.insert(category.to_string(), distribution);
+            }
+
+            // Distributions can go outside the puzzle's ratings, so search
+            // every rating there is
+            let regions = accepted_regions(&workflows, &categories, Interval::new(0, u32::MAX))
+                .unwrap_or_else(|err| panic!("{}", err));
+            let res = probability::acceptance_probability(&regions, &categories, &distributions)
+                .unwrap_or_else(|err| panic!("{}", err));
+            println!("Probability: {}", res);
+            return;
+        }
+        _ => {}
+    }
+
     let res = solution(input);
 
     println!("Result: {}", res);
@@ -213,6 +432,84 @@ mod tests {
         assert_eq!(res, 167409079868000);
     }
 
+    /// Accepted count for the given workflows, with no parts listed
+    fn count(workflows: &str) -> u128 {
+        solution(&format!("{}\n\n", workflows))
+    }
+
+    #[test]
+    fn test_repeated_conditions() {
+        let rest = 4000u128.pow(3);
+
+        // A looser condition later on doesn't undo an earlier, tighter one
+        assert_eq!(count("in{x>100:a,R}\na{x>50:A,R}"), 3900 * rest);
+        assert_eq!(count("in{x<100:a,R}\na{x<200:A,R}"), 99 * rest);
+
+        // Nor does a tighter one get lost when going past a looser one
+        assert_eq!(count("in{x>2000:R,x>1000:R,A}"), 1000 * rest);
+        assert_eq!(count("in{x>1000:a,R}\na{x<500:A,x<2000:A,R}"), 999 * rest);
+
+        // Conditions on different categories narrow each independently
+        assert_eq!(
+            count("in{x>100:a,R}\na{m<11:b,R}\nb{x<201:A,R}"),
+            100 * 10 * 4000 * 4000
+        );
+    }
+
+    #[test]
+    fn test_richer_conditions() {
+        let categories = ["weight", "colour"].map(String::from);
+        let count = |workflows: &str, range: Interval| {
+            count_accepted(&parse_workflows(workflows), &categories, range)
+        };
+
+        // Weights 5 to 10, and colours 1 to 10 apart from 3
+        let range = Interval::new(1, 10);
+        let workflows = "in{weight<=4:R,weight>=11:R,colour!=3:A,R}";
+        assert_eq!(count(workflows, range), Ok(6 * 9));
+        let workflows = "in{colour==3:R,weight>4:a,R}\na{weight<11:A,R}";
+        assert_eq!(count(workflows, range), Ok(6 * 9));
+
+        // Values can go right up to the top of a u32
+        let range = Interval::new(0, u32::MAX);
+        let workflows = "in{weight>4294967294:A,colour>=4294967295:A,R}";
+        assert_eq!(count(workflows, range), Ok((1 << 32) + (1 << 32) - 1));
+
+        // Two categories over every u32 make more parts than a u64 can count
+        assert_eq!(count("in{A}", range), Ok(1 << 64));
+
+        assert!(count("in{size>1:A,R}", range).is_err());
+    }
+
+    #[test]
+    fn test_declared_categories() {
+        assert_eq!(
+            declared_categories("{weight=1,colour=2}\n{colour=3,weight=4}"),
+            Ok(vec!["weight".to_string(), "colour".to_string()])
+        );
+        assert_eq!(declared_categories("").unwrap().len(), 4);
+        assert!(declared_categories("{a=1,a=2}").is_err());
+
+        assert_eq!(
+            declared_categories("{Weight=1}"),
+            Err(
+                r#"Category "Weight" isn't all lowercase letters, so no rule can use it"#
+                    .to_string()
+            )
+        );
+        assert!(declared_categories("{a\"b=1}").is_err());
+        assert!(declared_categories("{=1}").is_err());
+    }
+
+    #[test]
+    fn test_unreachable_rules() {
+        // Nothing left with x below 10 can have x below 5, so the second rule
+        // never fires and its region is dropped
+        assert_eq!(count("in{x<10:R,x<5:A,A}"), 3991 * 4000u128.pow(3));
+        assert_eq!(count("in{x<4001:R,A}"), 0);
+        assert_eq!(count("in{x>0:A,R}"), 4000u128.pow(4));
+    }
+
     #[test]
     fn test_input() {
         let input = include_str!("../input.txt");
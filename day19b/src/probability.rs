@@ -0,0 +1,208 @@
+//! How likely a part is to be accepted, when its ratings are drawn at random
+//! rather than all equally counted.
+//!
+//! Each category's rating is drawn independently, so the chance of landing in
+//! an accepted region is the product of the chances of landing in its range in
+//! each category. The regions never overlap, so the chance of acceptance is
+//! the sum of those over every region.
+
+use std::collections::HashMap;
+
+use crate::export::AcceptedRegion;
+use crate::region::Interval;
+
+/// How a single category's rating is distributed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Distribution {
+    /// Every rating in the range is equally likely
+    Uniform(Interval),
+    /// Each bin's weight is spread evenly over its range. The weights don't
+    /// need to add up to one, only be relative to each other, and the bins
+    /// mustn't overlap.
+    Histogram(Vec<(Interval, f64)>),
+}
+
+impl Distribution {
+    /// Chance of a rating falling within the interval
+    pub fn probability(&self, interval: Interval) -> f64 {
+        let within = |range: &Interval| {
+            if range.is_empty() {
+                0.0
+            } else {
+                range.intersect(&interval).len() as f64 / range.len() as f64
+            }
+        };
+
+        match self {
+            Distribution::Uniform(range) => within(range),
+            Distribution::Histogram(bins) => {
+                let total: f64 = bins.iter().map(|(_, weight)| weight).sum();
+                if total <= 0.0 {
+                    return 0.0;
+                }
+
+                bins.iter()
+                    .map(|(range, weight)| weight * within(range))
+                    .sum::<f64>()
+                    / total
+            }
+        }
+    }
+
+    /// Parse a uniform range like `1-4000`, or a histogram of weighted ranges
+    /// like `1-100:3,101-4000:1`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let range = |text: &str| {
+            let (lo, hi) = text
+                .split_once('-')
+                .ok_or_else(|| format!("Expected a range like 1-4000 but found {}", text))?;
+            let lo = lo.parse().map_err(|_| format!("Invalid rating {}", lo))?;
+            let hi = hi.parse().map_err(|_| format!("Invalid rating {}", hi))?;
+            if lo > hi {
+                return Err(format!("Range {} is empty", text));
+            }
+            Ok(Interval::new(lo, hi))
+        };
+
+        if !text.contains(':') {
+            return Ok(Distribution::Uniform(range(text)?));
+        }
+
+        let mut bins: Vec<(Interval, f64)> = vec![];
+        for bin in text.split(',') {
+            let (interval, weight) = bin
+                .split_once(':')
+                .ok_or_else(|| format!("Expected range:weight but found {}", bin))?;
+            let interval = range(interval)?;
+            let weight: f64 = weight
+                .parse()
+                .map_err(|_| format!("Invalid weight {}", weight))?;
+            if !(weight >= 0.0 && weight.is_finite()) {
+                return Err(format!("Invalid weight {}", weight));
+            }
+            if bins.iter().any(|(b, _)| !b.intersect(&interval).is_empty()) {
+                return Err(format!("Bin {} overlaps another", bin));
+            }
+            bins.push((interval, weight));
+        }
+
+        Ok(Distribution::Histogram(bins))
+    }
+}
+
+/// Chance of a part being accepted, given the regions the workflows accept
+/// and how each category is distributed. The regions need to cover every
+/// rating the distributions can give, or parts outside them count as
+/// rejected.
+pub fn acceptance_probability(
+    regions: &[AcceptedRegion],
+    categories: &[String],
+    distributions: &HashMap<String, Distribution>,
+) -> Result<f64, String> {
+    let distributions: Vec<&Distribution> = categories
+        .iter()
+        .map(|category| {
+            distributions
+                .get(category)
+                .ok_or_else(|| format!("No distribution for {}", category))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(regions
+        .iter()
+        .map(|accepted| {
+            categories
+                .iter()
+                .zip(&distributions)
+                .map(|(category, distribution)| {
+                    distribution.probability(accepted.region.bound(category))
+                })
+                .product::<f64>()
+        })
+        .sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{accepted_regions, count_accepted, declared_categories, parse_workflows};
+
+    fn uniform(categories: &[String]) -> HashMap<String, Distribution> {
+        categories
+            .iter()
+            .map(|c| (c.clone(), Distribution::Uniform(Interval::full())))
+            .collect()
+    }
+
+    #[test]
+    fn test_uniform_matches_count() {
+        for input in [include_str!("../example.txt"), include_str!("../input.txt")] {
+            let (workflows, parts) = input.split_once("\n\n").unwrap();
+            let workflows = parse_workflows(workflows);
+            let categories = declared_categories(parts).unwrap();
+
+            let regions = accepted_regions(&workflows, &categories, Interval::full()).unwrap();
+            let probability =
+                acceptance_probability(&regions, &categories, &uniform(&categories)).unwrap();
+            let count = count_accepted(&workflows, &categories, Interval::full()).unwrap();
+
+            let expected = count as f64 / 4000f64.powi(4);
+            assert!((probability - expected).abs() < 1e-12, "{}", probability);
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        let workflows = parse_workflows("in{x<11:A,m>3000:A,R}");
+        let categories = ["x", "m"].map(String::from);
+        let regions = accepted_regions(&workflows, &categories, Interval::full()).unwrap();
+
+        // Half the weight on x in 1 to 20, so a quarter is below 11, and m is
+        // never above 3000
+        let mut distributions = uniform(&categories);
+        distributions.insert(
+            "x".to_string(),
+            Distribution::parse("1-20:1,21-4000:1").unwrap(),
+        );
+        distributions.insert("m".to_string(), Distribution::parse("1-3000").unwrap());
+        let probability = acceptance_probability(&regions, &categories, &distributions).unwrap();
+        assert!((probability - 0.25).abs() < 1e-12, "{}", probability);
+
+        // With m above 3000 half the time, the rest of x adds to it
+        distributions.insert(
+            "m".to_string(),
+            Distribution::Histogram(vec![
+                (Interval::new(1, 3000), 1.0),
+                (Interval::new(3001, 4000), 1.0),
+            ]),
+        );
+        let probability = acceptance_probability(&regions, &categories, &distributions).unwrap();
+        assert!(
+            (probability - (0.25 + 0.75 * 0.5)).abs() < 1e-12,
+            "{}",
+            probability
+        );
+
+        distributions.remove("m");
+        assert!(acceptance_probability(&regions, &categories, &distributions).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Distribution::parse("5-10"),
+            Ok(Distribution::Uniform(Interval::new(5, 10)))
+        );
+        assert_eq!(
+            Distribution::parse("1-2:0.5,3-3:2"),
+            Ok(Distribution::Histogram(vec![
+                (Interval::new(1, 2), 0.5),
+                (Interval::new(3, 3), 2.0)
+            ]))
+        );
+        assert!(Distribution::parse("10-5").is_err());
+        assert!(Distribution::parse("1-10:1,5-20:1").is_err());
+        assert!(Distribution::parse("1-10:-1").is_err());
+        assert!(Distribution::parse("1-10:x").is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! Ranges of ratings, and regions of parts made of one range per category.
+
+use std::collections::BTreeMap;
+
+/// Lowest and highest rating any category can have in the puzzle
+pub const MIN_RATING: u32 = 1;
+pub const MAX_RATING: u32 = 4000;
+
+/// Inclusive range of ratings. Empty whenever `lo` is above `hi`.
+#[derive(Debug, Clone, Copy)]
+pub struct Interval {
+    pub lo: u32,
+    pub hi: u32,
+}
+
+impl Interval {
+    pub fn new(lo: u32, hi: u32) -> Self {
+        Self { lo, hi }
+    }
+
+    /// Every rating a category can have in the puzzle
+    pub fn full() -> Self {
+        Self::new(MIN_RATING, MAX_RATING)
+    }
+
+    /// A range with nothing in it
+    pub fn empty() -> Self {
+        Self::new(1, 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lo > self.hi
+    }
+
+    /// Number of ratings in the range
+    pub fn len(&self) -> u64 {
+        if self.is_empty() {
+            0
+        } else {
+            (self.hi - self.lo) as u64 + 1
+        }
+    }
+
+    pub fn intersect(&self, other: &Interval) -> Interval {
+        Self::new(self.lo.max(other.lo), self.hi.min(other.hi))
+    }
+
+    /// Values outside this range, as the parts below and above it. Either can
+    /// be empty.
+    pub fn complement(&self) -> [Interval; 2] {
+        if self.is_empty() {
+            return [Self::new(0, u32::MAX), Self::empty()];
+        }
+
+        [
+            self.lo
+                .checked_sub(1)
+                .map_or(Self::empty(), |hi| Self::new(0, hi)),
+            self.hi
+                .checked_add(1)
+                .map_or(Self::empty(), |lo| Self::new(lo, u32::MAX)),
+        ]
+    }
+}
+
+/// All empty intervals are the same, whatever their bounds
+impl PartialEq for Interval {
+    fn eq(&self, other: &Self) -> bool {
+        (self.is_empty() && other.is_empty()) || (self.lo == other.lo && self.hi == other.hi)
+    }
+}
+
+impl Eq for Interval {}
+
+/// A range of ratings in each category. Categories that haven't been narrowed
+/// down yet cover the whole of `range`, so a region doesn't need to know them
+/// all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hyperrectangle {
+    range: Interval,
+    bounds: BTreeMap<String, Interval>,
+}
+
+impl Hyperrectangle {
+    /// Every part with all its ratings in `range`
+    pub fn new(range: Interval) -> Self {
+        Self {
+            range,
+            bounds: BTreeMap::new(),
+        }
+    }
+
+    /// The range of ratings the region covers in a category
+    pub fn bound(&self, category: &str) -> Interval {
+        self.bounds.get(category).copied().unwrap_or(self.range)
+    }
+
+    /// Whether there are no parts in the region, which is when any category has
+    /// no ratings
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty() || self.bounds.values().any(Interval::is_empty)
+    }
+
+    /// Number of distinct parts in the region, for parts with these
+    /// categories. Two categories over every u32 already make 2^64, so this
+    /// needs more than a u64.
+    pub fn volume(&self, categories: &[String]) -> u128 {
+        categories
+            .iter()
+            .map(|c| self.bound(c).len() as u128)
+            .product()
+    }
+
+    pub fn intersect(&self, other: &Hyperrectangle) -> Hyperrectangle {
+        let mut region = Self::new(self.range.intersect(&other.range));
+        for category in self.bounds.keys().chain(other.bounds.keys()) {
+            let bound = self.bound(category).intersect(&other.bound(category));
+            region.bounds.insert(category.clone(), bound);
+        }
+
+        region
+    }
+
+    /// The parts of the region whose rating in one category is in the interval
+    pub fn restrict(&self, category: &str, interval: Interval) -> Hyperrectangle {
+        let mut other = Self::new(Interval::new(0, u32::MAX));
+        other.bounds.insert(category.to_string(), interval);
+
+        self.intersect(&other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xmas() -> Vec<String> {
+        ["x", "m", "a", "s"].map(String::from).to_vec()
+    }
+
+    #[test]
+    fn test_interval() {
+        let a = Interval::new(10, 20);
+        let b = Interval::new(15, 30);
+
+        assert_eq!(a.intersect(&b), Interval::new(15, 20));
+        assert_eq!(a.len(), 11);
+        assert!(a.intersect(&Interval::new(21, 30)).is_empty());
+        assert_eq!(Interval::new(5, 4).len(), 0);
+        assert_eq!(Interval::new(5, 4), Interval::new(3000, 2));
+        assert_eq!(Interval::new(0, u32::MAX).len(), 1 << 32);
+
+        assert_eq!(
+            a.complement(),
+            [Interval::new(0, 9), Interval::new(21, u32::MAX)]
+        );
+        let [below, above] = Interval::new(0, 100).complement();
+        assert!(below.is_empty());
+        assert_eq!(above, Interval::new(101, u32::MAX));
+        assert!(Interval::new(0, u32::MAX)
+            .complement()
+            .iter()
+            .all(Interval::is_empty));
+        assert_eq!(Interval::empty().complement()[0].len(), 1 << 32);
+    }
+
+    #[test]
+    fn test_hyperrectangle() {
+        let full = Hyperrectangle::new(Interval::full());
+        assert_eq!(full.volume(&xmas()), 4000u128.pow(4));
+
+        let region = full
+            .restrict("x", Interval::new(1, 10))
+            .restrict("s", Interval::new(5, 6));
+        assert_eq!(region.volume(&xmas()), 10 * 2 * 4000 * 4000);
+
+        // Restricting the same category again narrows it further, never widens
+        let narrower = region.restrict("x", Interval::new(5, 4000));
+        assert_eq!(narrower.bound("x"), Interval::new(5, 10));
+
+        let empty = region.restrict("x", Interval::new(11, 4000));
+        assert!(empty.is_empty());
+        assert_eq!(empty.volume(&xmas()), 0);
+
+        // Going past the range of ratings doesn't add any parts
+        let wide = full.restrict("m", Interval::new(3000, 5000));
+        assert_eq!(wide.bound("m"), Interval::new(3000, 4000));
+
+        let both = region.intersect(&wide);
+        assert_eq!(both.volume(&xmas()), 10 * 1001 * 4000 * 2);
+    }
+}
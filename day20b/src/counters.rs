@@ -0,0 +1,512 @@
+//! Recognising the counters that drive `rx`, and working out when they first
+//! all line up.
+//!
+//! `rx` has to be fed by a single conjunction, the sink, which only sends it a
+//! low pulse once every one of its inputs has last sent it a high. Each of the
+//! broadcaster's outputs starts a subgraph of its own, which in the puzzle
+//! input is a binary counter of flip-flops with a conjunction feeding back
+//! into it, and each subgraph has exactly one input to the sink, its tap. As
+//! long as the subgraphs don't share any modules, no pulse can pass from one
+//! to another, so each can be simulated on its own to find how often its tap
+//! fires. The sink fires on the first press where they all do.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::{outputs, simulate_button_press, Module, Pulse};
+
+/// Most presses to simulate while waiting for a counter's tap to first fire
+pub const MAX_PRESSES: u64 = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StructureError {
+    NoBroadcaster,
+    /// Nothing sends pulses to rx
+    NoRx,
+    /// There's more than one module sending pulses to rx
+    RxFeeders {
+        feeders: Vec<String>,
+    },
+    /// The module feeding rx isn't a conjunction
+    NotConjunction {
+        name: String,
+    },
+    /// The conjunction feeding rx sends pulses somewhere else as well, which
+    /// could feed back into the counters
+    SinkOutputs {
+        sink: String,
+    },
+    /// A module can be reached from more than one of the broadcaster's outputs
+    Shared {
+        module: String,
+        starts: [String; 2],
+    },
+    /// The subgraph from a broadcaster output doesn't have exactly one input
+    /// to the sink
+    Taps {
+        start: String,
+        taps: Vec<String>,
+    },
+    /// An input to the sink that none of the broadcaster's outputs lead to
+    Untapped {
+        input: String,
+    },
+    /// The tap didn't fire within `MAX_PRESSES` presses
+    NeverFires {
+        start: String,
+    },
+    /// After the tap first fires, it doesn't keep firing with that same period
+    Unclean {
+        start: String,
+        period: u64,
+    },
+    /// The counters don't all fire on the same press until after more presses
+    /// than fit in a u64
+    TooManyPresses,
+}
+
+impl fmt::Display for StructureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StructureError::NoBroadcaster => write!(f, "there's no broadcaster module"),
+            StructureError::NoRx => write!(f, "nothing sends pulses to rx"),
+            StructureError::RxFeeders { feeders } => write!(
+                f,
+                "rx should have one conjunction feeding it but has {}",
+                feeders.join(", ")
+            ),
+            StructureError::NotConjunction { name } => {
+                write!(f, "{} feeds rx but isn't a conjunction", name)
+            }
+            StructureError::SinkOutputs { sink } => {
+                write!(f, "{} feeds rx but sends pulses elsewhere too", sink)
+            }
+            StructureError::Shared { module, starts } => write!(
+                f,
+                "{} can be reached from both {} and {}",
+                module, starts[0], starts[1]
+            ),
+            StructureError::Taps { start, taps } if taps.is_empty() => {
+                write!(f, "nothing reached from {} feeds into rx", start)
+            }
+            StructureError::Taps { start, taps } => write!(
+                f,
+                "{} leads to more than one input to rx: {}",
+                start,
+                taps.join(", ")
+            ),
+            StructureError::Untapped { input } => write!(
+                f,
+                "{} feeds into rx but can't be reached from the broadcaster",
+                input
+            ),
+            StructureError::NeverFires { start } => write!(
+                f,
+                "counter from {} didn't fire within {} presses",
+                start, MAX_PRESSES
+            ),
+            StructureError::Unclean { start, period } => write!(
+                f,
+                "counter from {} first fires after {} presses but doesn't repeat with that period",
+                start, period
+            ),
+            StructureError::TooManyPresses => write!(
+                f,
+                "the counters don't all fire together within {} presses",
+                u64::MAX
+            ),
+        }
+    }
+}
+
+/// One of the independent subgraphs feeding rx
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counter {
+    /// The broadcaster output the subgraph starts from
+    pub start: String,
+    /// The subgraph's input to the conjunction feeding rx
+    pub tap: String,
+    /// Every module in the subgraph, sorted
+    pub modules: Vec<String>,
+    /// The tap sends a high pulse to the sink on every press that's a
+    /// multiple of this, and on no others
+    pub period: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// The conjunction feeding rx
+    pub sink: String,
+    pub counters: Vec<Counter>,
+    presses: u64,
+}
+
+impl Analysis {
+    /// Fewest presses for every counter to fire on the same press, sending a
+    /// low pulse to rx
+    pub fn presses(&self) -> u64 {
+        self.presses
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Lowest common multiple, if it fits in a u64
+fn lcm(a: u64, b: u64) -> Option<u64> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+/// Check the modules have the structure described above, and find the period
+/// of each counter
+pub fn analyse(modules: &HashMap<String, Module>) -> Result<Analysis, StructureError> {
+    let Some(Module::Broadcaster { outputs: starts }) = modules.get("broadcaster") else {
+        return Err(StructureError::NoBroadcaster);
+    };
+
+    let mut feeders: Vec<&String> = modules
+        .iter()
+        .filter(|(_, module)| outputs(module).iter().any(|o| o == "rx"))
+        .map(|(name, _)| name)
+        .collect();
+    feeders.sort();
+    let sink = match feeders[..] {
+        [] => return Err(StructureError::NoRx),
+        [sink] => sink,
+        _ => {
+            return Err(StructureError::RxFeeders {
+                feeders: feeders.into_iter().cloned().collect(),
+            })
+        }
+    };
+
+    let Module::Conjunction(conjunction) = &modules[sink] else {
+        return Err(StructureError::NotConjunction { name: sink.clone() });
+    };
+    if outputs(&modules[sink]) != ["rx"] {
+        return Err(StructureError::SinkOutputs { sink: sink.clone() });
+    }
+    let inputs: HashSet<&String> = conjunction.inputs().collect();
+
+    // Which broadcaster output each module can be reached from
+    let mut owners: HashMap<&str, &str> = HashMap::new();
+    let subgraphs: Vec<Vec<&str>> = starts
+        .iter()
+        .map(|start| subgraph(modules, start, sink))
+        .collect();
+
+    for (start, subgraph) in starts.iter().zip(&subgraphs) {
+        for &module in subgraph {
+            if let Some(owner) = owners.insert(module, start) {
+                return Err(StructureError::Shared {
+                    module: module.to_string(),
+                    starts: [owner.to_string(), start.clone()],
+                });
+            }
+        }
+    }
+
+    let mut counters = vec![];
+    for (start, subgraph) in starts.iter().zip(&subgraphs) {
+        let mut taps: Vec<String> = subgraph
+            .iter()
+            .filter(|&&module| inputs.contains(&module.to_string()))
+            .map(|module| module.to_string())
+            .collect();
+        taps.sort();
+        if taps.len() != 1 {
+            return Err(StructureError::Taps {
+                start: start.clone(),
+                taps,
+            });
+        }
+
+        let mut names: Vec<String> = subgraph.iter().map(|module| module.to_string()).collect();
+        names.sort();
+        counters.push(Counter {
+            start: start.clone(),
+            tap: taps.remove(0),
+            modules: names,
+            period: 0,
+        });
+    }
+
+    let mut untapped: Vec<&&String> = inputs
+        .iter()
+        .filter(|input| !owners.contains_key(input.as_str()))
+        .collect();
+    untapped.sort();
+    if let Some(input) = untapped.first() {
+        return Err(StructureError::Untapped {
+            input: input.to_string(),
+        });
+    }
+
+    for counter in &mut counters {
+        counter.period = period(modules, counter, sink)?;
+    }
+
+    let presses = counters
+        .iter()
+        .try_fold(1, |accum, counter| lcm(accum, counter.period))
+        .ok_or(StructureError::TooManyPresses)?;
+
+    Ok(Analysis {
+        sink: sink.clone(),
+        counters,
+        presses,
+    })
+}
+
+/// Every module that pulses from `start` can reach without going through the
+/// sink, including `start` itself
+fn subgraph<'a>(modules: &'a HashMap<String, Module>, start: &'a str, sink: &str) -> Vec<&'a str> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut frontier = VecDeque::new();
+
+    if start != sink {
+        seen.insert(start);
+        frontier.push_back(start);
+    }
+
+    while let Some(name) = frontier.pop_front() {
+        for output in outputs(&modules[name]) {
+            if output != sink && output != "broadcaster" && seen.insert(output) {
+                frontier.push_back(output);
+            }
+        }
+    }
+
+    let mut subgraph: Vec<&str> = seen.into_iter().collect();
+    subgraph.sort();
+    subgraph
+}
+
+/// Press the button with only the counter's start getting the broadcaster's
+/// pulse, until its tap first sends a high pulse to the sink. Then check the
+/// next firing comes exactly that many presses later, leaving the counter in
+/// the same state as after the first, so it keeps firing at that period.
+fn period(
+    modules: &HashMap<String, Module>,
+    counter: &Counter,
+    sink: &str,
+) -> Result<u64, StructureError> {
+    let mut modules = modules.clone();
+    let targets = [counter.start.clone()];
+
+    let press = |modules: &mut HashMap<String, Module>| {
+        let mut fired = false;
+        simulate_button_press(modules, &targets, |from, to, pulse| {
+            if from == counter.tap && to == sink && pulse == Pulse::High {
+                fired = true;
+            }
+        });
+        fired
+    };
+    let state = |modules: &HashMap<String, Module>| -> Vec<Module> {
+        counter
+            .modules
+            .iter()
+            .map(|name| modules[name].clone())
+            .collect()
+    };
+
+    let Some(period) = (1..=MAX_PRESSES).find(|_| press(&mut modules)) else {
+        return Err(StructureError::NeverFires {
+            start: counter.start.clone(),
+        });
+    };
+
+    let unclean = || StructureError::Unclean {
+        start: counter.start.clone(),
+        period,
+    };
+    let first = state(&modules);
+    for n in 1..=period {
+        if press(&mut modules) != (n == period) {
+            return Err(unclean());
+        }
+    }
+    if state(&modules) != first {
+        return Err(unclean());
+    }
+
+    Ok(period)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_modules;
+
+    /// A counter of flip-flops named `{prefix}0` upwards, from least to most
+    /// significant bit. The bits marked true feed a conjunction, which feeds
+    /// back into the rest of them and the lowest bit, and is inverted on its
+    /// way to the sink `tj`.
+    fn counter(prefix: &str, feeds: &[bool]) -> String {
+        let mut lines = vec![];
+        let mut feedback = vec![format!("{}0", prefix)];
+
+        for (i, &feed) in feeds.iter().enumerate() {
+            let mut outputs = vec![];
+            if i + 1 < feeds.len() {
+                outputs.push(format!("{}{}", prefix, i + 1));
+            }
+            if feed || i == 0 {
+                outputs.push(format!("{}c", prefix));
+            } else {
+                feedback.push(format!("{}{}", prefix, i));
+            }
+            lines.push(format!("%{}{} -> {}", prefix, i, outputs.join(", ")));
+        }
+
+        feedback.push(format!("{}i", prefix));
+        lines.push(format!("&{}c -> {}", prefix, feedback.join(", ")));
+        lines.push(format!("&{}i -> tj", prefix));
+        lines.join("\n")
+    }
+
+    fn network(counters: &[(&str, &[bool])]) -> String {
+        let starts: Vec<String> = counters.iter().map(|(p, _)| format!("{}0", p)).collect();
+        let mut lines = vec![format!("broadcaster -> {}", starts.join(", "))];
+        lines.extend(counters.iter().map(|(p, feeds)| counter(p, feeds)));
+        lines.push("&tj -> rx".to_string());
+        lines.join("\n")
+    }
+
+    /// Press the button until rx gets a low pulse, simulating everything
+    fn brute_force(input: &str) -> u64 {
+        let mut modules = load_modules(input);
+        let Module::Broadcaster { outputs } = modules["broadcaster"].clone() else {
+            unreachable!()
+        };
+
+        for presses in 1.. {
+            let mut low = false;
+            simulate_button_press(&mut modules, &outputs, |_, to, pulse| {
+                low |= to == "rx" && pulse == Pulse::Low;
+            });
+            if low {
+                return presses;
+            }
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn test_input() {
+        let analysis = analyse(&load_modules(include_str!("../input.txt"))).unwrap();
+
+        assert_eq!(analysis.sink, "tj");
+        let mut periods: Vec<u64> = analysis.counters.iter().map(|c| c.period).collect();
+        periods.sort();
+        assert_eq!(periods, [3917, 3931, 3943, 4057]);
+        assert!(analysis.counters.iter().all(|c| c.modules.len() == 14));
+        assert_eq!(analysis.presses(), 246313604784977);
+    }
+
+    #[test]
+    fn test_small_counters() {
+        let input = network(&[
+            ("a", &[true, false, true, true]),
+            ("b", &[true, true, false, true]),
+            ("c", &[true, true, true]),
+        ]);
+        let analysis = analyse(&load_modules(&input)).unwrap();
+
+        let periods: Vec<u64> = analysis.counters.iter().map(|c| c.period).collect();
+        assert_eq!(periods, [13, 11, 7]);
+        assert_eq!(analysis.presses(), 1001);
+        assert_eq!(analysis.presses(), brute_force(&input));
+    }
+
+    #[test]
+    fn test_too_many_presses() {
+        // Five 13 bit primes multiply to more than a u64 holds
+        let periods: [u64; 5] = [8191, 8179, 8171, 8167, 8161];
+        let feeds: Vec<Vec<bool>> = periods
+            .iter()
+            .map(|period| (0..13).map(|bit| period >> bit & 1 == 1).collect())
+            .collect();
+        let prefixes = ["a", "b", "c", "d", "e"];
+        let counters: Vec<(&str, &[bool])> = prefixes
+            .iter()
+            .zip(&feeds)
+            .map(|(&prefix, feeds)| (prefix, feeds.as_slice()))
+            .collect();
+
+        let res = analyse(&load_modules(&network(&counters[..4])));
+        assert_eq!(
+            res.map(|analysis| analysis.presses()),
+            Ok(periods[..4].iter().product())
+        );
+
+        let res = analyse(&load_modules(&network(&counters)));
+        assert_eq!(res, Err(StructureError::TooManyPresses));
+    }
+
+    #[test]
+    fn test_wrong_structure() {
+        let err = |input: &str| analyse(&load_modules(input)).unwrap_err();
+
+        assert_eq!(err(include_str!("../example_1.txt")), StructureError::NoRx);
+        assert_eq!(
+            err("broadcaster -> a\n%a -> rx, b\n%b -> rx"),
+            StructureError::RxFeeders {
+                feeders: vec!["a".to_string(), "b".to_string()]
+            }
+        );
+        assert_eq!(
+            err("broadcaster -> a\n%a -> rx"),
+            StructureError::NotConjunction {
+                name: "a".to_string()
+            }
+        );
+
+        // A counter feeding another
+        let input = network(&[("a", &[true, true]), ("b", &[true, true])]);
+        let input = input.replace("%a1 -> ac", "%a1 -> ac, bc");
+        assert_eq!(
+            err(&input),
+            StructureError::Shared {
+                module: "b0".to_string(),
+                starts: ["a0".to_string(), "b0".to_string()]
+            }
+        );
+
+        // A counter that never makes it to rx
+        let input = network(&[("a", &[true, true]), ("b", &[true, true])]);
+        let input = input.replace("&bi -> tj", "&bi -> bc");
+        assert_eq!(
+            err(&input),
+            StructureError::Taps {
+                start: "b0".to_string(),
+                taps: vec![]
+            }
+        );
+
+        // One of the input's counters left without a pulse from the broadcaster
+        let mut modules = load_modules(include_str!("../input.txt"));
+        if let Some(Module::Broadcaster { outputs }) = modules.get_mut("broadcaster") {
+            outputs.truncate(3);
+        }
+        assert!(matches!(
+            analyse(&modules),
+            Err(StructureError::Untapped { .. })
+        ));
+
+        // A flip-flop fires on every other press after its first
+        assert_eq!(
+            err("broadcaster -> a\n%a -> tj\n&tj -> rx"),
+            StructureError::Unclean {
+                start: "a".to_string(),
+                period: 1
+            }
+        );
+    }
+}
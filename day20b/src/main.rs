@@ -1,29 +1,96 @@
-use std::collections::HashMap;
+mod counters;
 
-#[derive(Debug)]
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    On,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pulse {
+    Low,
+    High,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct FlipFlop {
     outputs: Vec<String>,
+    state: State,
 }
 
 impl FlipFlop {
     fn new(outputs: Vec<String>) -> Self {
-        Self { outputs }
+        Self {
+            outputs,
+            state: State::Off,
+        }
+    }
+
+    fn process(&mut self, input: Pulse) -> Pulse {
+        match (input, &self.state) {
+            (Pulse::High, _) => Pulse::None,
+            (Pulse::Low, &State::On) => {
+                self.state = State::Off;
+                Pulse::Low
+            }
+            (Pulse::Low, &State::Off) => {
+                self.state = State::On;
+                Pulse::High
+            }
+            (Pulse::None, _) => Pulse::None,
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Conjunction {
     outputs: Vec<String>,
+    memory: HashMap<String, State>,
 }
 
 // An active-low AND gate
 impl Conjunction {
     fn new(outputs: Vec<String>) -> Self {
-        Self { outputs }
+        Self {
+            outputs,
+            memory: HashMap::new(),
+        }
+    }
+
+    fn add_inputs(&mut self, inputs: Vec<String>) {
+        for input in inputs {
+            self.memory.insert(input, State::Off);
+        }
+    }
+
+    /// Names of the modules that send pulses to this one
+    fn inputs(&self) -> impl Iterator<Item = &String> {
+        self.memory.keys()
+    }
+
+    fn process(&mut self, from: &str, input: Pulse) -> Pulse {
+        match input {
+            Pulse::None => return Pulse::None,
+            Pulse::High => {
+                *self.memory.get_mut(from).unwrap() = State::On;
+            }
+            Pulse::Low => {
+                *self.memory.get_mut(from).unwrap() = State::Off;
+            }
+        }
+
+        if self.memory.iter().all(|(_, state)| state == &State::On) {
+            Pulse::Low
+        } else {
+            Pulse::High
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Module {
     FlipFlop(FlipFlop),
     Conjunction(Conjunction),
@@ -56,25 +123,89 @@ fn load_modules(input: &str) -> HashMap<String, Module> {
         }
     }
 
+    // With modules loaded, we need to do a pass through to set up the inputs
+    // for the conjunctions and any output-only modules. Because rust, we need
+    // to do this as two separate loops to avoid mutating modules while
+    // iterating over it
     let mut new_outputs: Vec<(String, Module)> = Vec::new();
+    let mut module_inputs: HashMap<String, Vec<String>> = HashMap::new();
 
-    for (_, module) in modules.iter() {
+    for (name, module) in modules.iter() {
         if let Module::FlipFlop(FlipFlop { outputs, .. })
-        | Module::Conjunction(Conjunction { outputs, .. }) = module
+        | Module::Conjunction(Conjunction { outputs, .. })
+        | Module::Broadcaster { outputs } = module
         {
             for o in outputs {
                 if !modules.contains_key(o) {
                     new_outputs.push((o.clone(), Module::Output));
                 }
+                module_inputs
+                    .entry(o.clone())
+                    .or_default()
+                    .push(name.clone());
             }
         }
     }
 
     modules.extend(new_outputs);
 
+    // Go through and set the inputs for the conjunction modules
+    for (name, inputs) in module_inputs {
+        if let Module::Conjunction(c) = modules
+            .get_mut(&name)
+            .unwrap_or_else(|| panic!("Unknown module {}", name))
+        {
+            c.add_inputs(inputs);
+        }
+    }
+
     modules
 }
 
+/// Names of the modules a module sends pulses to
+fn outputs(module: &Module) -> &[String] {
+    match module {
+        Module::FlipFlop(FlipFlop { outputs, .. })
+        | Module::Conjunction(Conjunction { outputs, .. })
+        | Module::Broadcaster { outputs } => outputs,
+        Module::Output => &[],
+    }
+}
+
+/// Simulate a button press, with the broadcaster only passing the low pulse
+/// on to `targets`. `on_pulse` is called with every pulse sent, as (from, to,
+/// pulse), in the order they arrive.
+fn simulate_button_press(
+    modules: &mut HashMap<String, Module>,
+    targets: &[String],
+    mut on_pulse: impl FnMut(&str, &str, Pulse),
+) {
+    // Tuples of (from, to, pulse)
+    let mut signals: VecDeque<(String, String, Pulse)> = VecDeque::new();
+
+    for target in targets {
+        signals.push_back(("broadcaster".to_string(), target.clone(), Pulse::Low));
+    }
+
+    while let Some((source, dest, pulse)) = signals.pop_front() {
+        on_pulse(&source, &dest, pulse);
+
+        if let Some((outputs, pulse)) = match modules.get_mut(&dest).unwrap() {
+            Module::FlipFlop(f) => Some((f.outputs.clone(), f.process(pulse))),
+            Module::Conjunction(c) => Some((c.outputs.clone(), c.process(&source, pulse))),
+            _ => None,
+        } {
+            if matches!(pulse, Pulse::None) {
+                continue;
+            }
+
+            for output in outputs {
+                signals.push_back((dest.clone(), output, pulse));
+            }
+        }
+    }
+}
+
 fn print_mermaid_diagram(modules: &HashMap<String, Module>) {
     println!("flowchart TB");
     println!("    broadcaster[[broadcaster]]");
@@ -128,9 +259,52 @@ fn print_mermaid_diagram(modules: &HashMap<String, Module>) {
     }
 }
 
-/// No computed solution for this part, see the readme for a proof by inspection.
 fn main() {
+    // Usage: day20b
+    //    or: day20b mermaid
+    //
+    // mermaid prints a diagram of the modules, as in the readme, rather than
+    // solving the puzzle.
     let input = include_str!("../input.txt");
     let modules = load_modules(input);
-    print_mermaid_diagram(&modules);
+
+    if std::env::args().nth(1).as_deref() == Some("mermaid") {
+        print_mermaid_diagram(&modules);
+        return;
+    }
+
+    // Simulating the whole network until rx gets a low pulse would take far
+    // too long. See the readme for how it's really a few counters, which
+    // this checks for before working out when they all line up.
+    let analysis = match counters::analyse(&modules) {
+        Ok(analysis) => analysis,
+        Err(err) => {
+            eprintln!("Can't solve these modules: {}", err);
+            std::process::exit(1);
+        }
+    };
+    for counter in &analysis.counters {
+        println!(
+            "Counter from {} through {}: {} modules, period {}",
+            counter.start,
+            counter.tap,
+            counter.modules.len(),
+            counter.period
+        );
+    }
+
+    println!("Result: {}", analysis.presses());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input() {
+        let modules = load_modules(include_str!("../input.txt"));
+        let res = counters::analyse(&modules).unwrap().presses();
+
+        assert_eq!(res, 246313604784977);
+    }
 }